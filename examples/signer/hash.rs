@@ -1,88 +1,5 @@
-use axum::{
-    Json, Router,
-    routing::{get, post},
-};
-use serde::{Deserialize, Serialize};
 use starknet_rust::core::types::{BroadcastedInvokeTransactionV3, Felt};
-use starknet_rust::signers::SigningKey;
 use starknet_rust_crypto::{PoseidonHasher, poseidon_hash_many};
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-#[derive(Serialize)]
-struct PublicKeyResponse {
-    public_key: Felt,
-}
-
-#[derive(Deserialize)]
-struct SignHashRequest {
-    transaction: BroadcastedInvokeTransactionV3,
-    chain_id: Felt,
-}
-
-#[derive(Serialize)]
-struct SignHashResponse {
-    signature: [Felt; 2],
-}
-
-#[tokio::main]
-async fn main() {
-    let format = tracing_subscriber::fmt::format().compact();
-    tracing_subscriber::fmt()
-        .event_format(format)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
-
-    let signing_key = SigningKey::from_secret_scalar(
-        Felt::from_hex(
-            &std::env::var("PRIVATE_KEY")
-                .expect("PRIVATE_KEY environment variable should be set to the private key"),
-        )
-        .unwrap(),
-    );
-
-    let state = Arc::new(Mutex::new(signing_key));
-
-    let app = Router::new()
-        .route(
-            "/get_public_key",
-            get({
-                let state: Arc<Mutex<SigningKey>> = Arc::clone(&state);
-                move || async move {
-                    Json(PublicKeyResponse {
-                        public_key: state.lock().await.verifying_key().scalar(),
-                    })
-                }
-            }),
-        )
-        .route(
-            "/sign",
-            post({
-                let state = Arc::clone(&state);
-                move |Json(payload): Json<SignHashRequest>| {
-                    let state = Arc::clone(&state);
-                    async move {
-                        let transaction_hash = transaction_hash(&payload.transaction, payload.chain_id);
-                        tracing::info!(transaction=?payload.transaction, chain_id=?payload.chain_id, ?transaction_hash, "Signing transaction");
-
-
-                        // Sign the hash
-                        let signing_key = state.lock().await;
-                        let signature = signing_key.sign(&transaction_hash).unwrap();
-
-                        Json(SignHashResponse {
-                            signature: [signature.r, signature.s],
-                        })
-                    }
-                }
-            }),
-        );
-
-    let listener = tokio::net::TcpListener::bind("localhost:3000")
-        .await
-        .unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
 
 /// Cairo string for "invoke"
 const PREFIX_INVOKE: Felt = Felt::from_raw([
@@ -101,7 +18,7 @@ const QUERY_VERSION_THREE: Felt = Felt::from_raw([
 ]);
 
 // Mostly a copy of the `RawExecutionV3::transaction_hash()` method from the starknet-rs.
-fn transaction_hash(tx: &BroadcastedInvokeTransactionV3, chain_id: Felt) -> Felt {
+pub fn transaction_hash(tx: &BroadcastedInvokeTransactionV3, chain_id: Felt) -> Felt {
     let mut hasher = PoseidonHasher::new();
 
     hasher.update(PREFIX_INVOKE);
@@ -0,0 +1,347 @@
+//! Reference implementation of the remote signer API.
+//!
+//! The signer only signs transactions consisting of a single `attest` call to
+//! the configured attestation contract, sent from the configured operational
+//! account on the configured chain, with bounded fees. Everything else is
+//! rejected with a structured error.
+//!
+//! Signed attestations are recorded in a protection database so that a
+//! misbehaving client cannot get conflicting attestations signed for the same
+//! nonce, block or epoch, nor more attestations than an epoch allows within a
+//! time window.
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{State, rejection::JsonRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use starknet_rust::core::types::{BroadcastedInvokeTransactionV3, Felt};
+use starknet_rust::signers::SigningKey;
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::Mutex;
+
+mod hash;
+mod policy;
+mod protection;
+
+use hash::transaction_hash;
+use policy::{ErrorResponse, Policy, PolicyViolation};
+use protection::{ProtectionDb, SigningRequest};
+
+#[derive(Parser)]
+struct Config {
+    #[arg(
+        long,
+        long_help = "The private key of the operational account.",
+        value_name = "KEY",
+        env = "PRIVATE_KEY",
+        hide_env_values = true
+    )]
+    private_key: Felt,
+
+    #[arg(
+        long,
+        long_help = "The address to listen on. Use unix:///path/to/socket to listen on a Unix \
+                     domain socket instead of TCP.",
+        default_value = "localhost:3000",
+        value_name = "ADDRESS",
+        env = "SIGNER_LISTEN_ADDRESS"
+    )]
+    listen_address: String,
+
+    #[arg(
+        long,
+        long_help = "The chain ID transactions are signed for, either as a hex value or as a \
+                     short string like SN_MAIN.",
+        value_name = "CHAIN_ID",
+        value_parser = parse_chain_id,
+        env = "SIGNER_CHAIN_ID"
+    )]
+    chain_id: Felt,
+
+    #[arg(
+        long,
+        long_help = "The address of the operational account.",
+        value_name = "ADDRESS",
+        env = "SIGNER_SENDER_ADDRESS"
+    )]
+    sender_address: Felt,
+
+    #[arg(
+        long,
+        long_help = "The address of the attestation contract.",
+        value_name = "ADDRESS",
+        env = "SIGNER_ATTESTATION_CONTRACT_ADDRESS"
+    )]
+    attestation_contract_address: Felt,
+
+    #[arg(
+        long,
+        long_help = "The maximum fee (including the tip) in FRI a signed transaction may be \
+                     charged.",
+        value_name = "FRI",
+        env = "SIGNER_MAX_FEE"
+    )]
+    max_fee: u128,
+
+    #[arg(
+        long,
+        long_help = "The maximum tip per L2 gas unit of a signed transaction.",
+        value_name = "FRI",
+        env = "SIGNER_MAX_TIP"
+    )]
+    max_tip: u64,
+
+    #[arg(
+        long,
+        long_help = "The file recording signed attestations.",
+        default_value = "signer-protection.json",
+        value_name = "PATH",
+        env = "SIGNER_PROTECTION_DB"
+    )]
+    protection_db: PathBuf,
+
+    #[arg(
+        long,
+        long_help = "The maximum number of attestation transactions signed per epoch. Raise \
+                     this to allow the client to retry reverted attestations. Clients using \
+                     the v1 protocol do not send the epoch, their attestations are limited per \
+                     attested block instead.",
+        default_value = "1",
+        value_name = "COUNT",
+        env = "SIGNER_MAX_SIGNATURES_PER_EPOCH"
+    )]
+    max_signatures_per_epoch: usize,
+
+    #[arg(
+        long,
+        long_help = "At most --max-signatures-per-epoch attestations are signed per sender \
+                     within this many seconds, whatever block hash and epoch the client \
+                     sends, as the signer cannot verify them. Set it below the duration of \
+                     an epoch.",
+        default_value = "300",
+        value_name = "SECONDS",
+        env = "SIGNER_SIGNATURE_WINDOW"
+    )]
+    signature_window: u64,
+}
+
+fn parse_chain_id(value: &str) -> anyhow::Result<Felt> {
+    if value.starts_with("0x") {
+        Ok(Felt::from_hex(value)?)
+    } else {
+        Ok(starknet_rust::core::utils::cairo_short_string_to_felt(
+            value,
+        )?)
+    }
+}
+
+#[derive(Serialize)]
+struct PublicKeyResponse {
+    public_key: Felt,
+}
+
+#[derive(Deserialize)]
+struct SignHashRequest {
+    transaction: BroadcastedInvokeTransactionV3,
+    chain_id: Felt,
+    // The fields below are only sent by clients using the v2 protocol.
+    transaction_hash: Option<Felt>,
+    attestation: Option<AttestationContext>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)] // Not all fields are checked, but all are logged.
+struct AttestationContext {
+    staker_address: Felt,
+    epoch_id: u64,
+    block_number: u64,
+    block_hash: Felt,
+    attestation_contract_address: Felt,
+}
+
+#[derive(Serialize)]
+struct SignHashResponse {
+    signature: [Felt; 2],
+}
+
+struct AppState {
+    signing_key: SigningKey,
+    policy: Policy,
+    protection_db: Mutex<ProtectionDb>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let format = tracing_subscriber::fmt::format().compact();
+    tracing_subscriber::fmt()
+        .event_format(format)
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::parse();
+
+    let state = Arc::new(AppState {
+        signing_key: SigningKey::from_secret_scalar(config.private_key),
+        policy: Policy {
+            chain_id: config.chain_id,
+            sender_address: config.sender_address,
+            attestation_contract_address: config.attestation_contract_address,
+            max_fee: config.max_fee,
+            max_tip: config.max_tip,
+        },
+        protection_db: Mutex::new(ProtectionDb::open(
+            &config.protection_db,
+            config.max_signatures_per_epoch,
+            config.signature_window,
+        )?),
+    });
+    tracing::info!(policy=?state.policy, "Starting signer");
+
+    let app = Router::new()
+        .route("/get_public_key", get(get_public_key))
+        .route("/sign", post(sign))
+        .with_state(state);
+
+    if let Some(socket_path) = config.listen_address.strip_prefix("unix://") {
+        let listener = bind_unix_socket(std::path::Path::new(socket_path))?;
+        tracing::info!(%socket_path, "Listening on Unix domain socket");
+        axum::serve(listener, app).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(&config.listen_address).await?;
+        tracing::info!(address=%config.listen_address, "Listening on TCP");
+        axum::serve(listener, app).await?;
+    }
+
+    Ok(())
+}
+
+/// Binds a Unix domain socket only processes running as the same user may
+/// connect to.
+///
+/// The socket is created with the process umask, so it is bound inside a
+/// private directory and only moved to `socket_path` once its permissions are
+/// restricted: there is no window in which other users can connect.
+fn bind_unix_socket(socket_path: &std::path::Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let file_name = socket_path
+        .file_name()
+        .context("Unix socket path has no file name")?;
+    let mut private_dir_name = std::ffi::OsString::from(".");
+    private_dir_name.push(file_name);
+    private_dir_name.push(format!(".{}", std::process::id()));
+    let private_dir = socket_path.with_file_name(private_dir_name);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .with_context(|| format!("Creating {}", private_dir.display()))?;
+
+    let result = (|| -> std::io::Result<_> {
+        let private_path = private_dir.join(file_name);
+        let listener = tokio::net::UnixListener::bind(&private_path)?;
+        std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+        // Replaces the socket left behind by a previous run.
+        std::fs::rename(&private_path, socket_path)?;
+        Ok(listener)
+    })();
+    let _ = std::fs::remove_dir_all(&private_dir);
+    result.with_context(|| format!("Binding Unix socket {}", socket_path.display()))
+}
+
+async fn get_public_key(State(state): State<Arc<AppState>>) -> Json<PublicKeyResponse> {
+    Json(PublicKeyResponse {
+        public_key: state.signing_key.verifying_key().scalar(),
+    })
+}
+
+async fn sign(
+    State(state): State<Arc<AppState>>,
+    payload: Result<Json<SignHashRequest>, JsonRejection>,
+) -> Result<Json<SignHashResponse>, Response> {
+    let Json(payload) = payload.map_err(|rejection| {
+        tracing::warn!(%rejection, "Malformed request");
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "MALFORMED_REQUEST",
+                rejection.body_text(),
+            )),
+        )
+            .into_response()
+    })?;
+
+    let transaction_hash = transaction_hash(&payload.transaction, payload.chain_id);
+    tracing::info!(transaction=?payload.transaction, chain_id=?payload.chain_id, ?transaction_hash, attestation=?payload.attestation, "Received signing request");
+
+    // Never trust the hash sent by the client, but refuse to sign if it
+    // disagrees with ours: the client would get an invalid transaction.
+    if let Some(sent) = payload.transaction_hash
+        && sent != transaction_hash
+    {
+        return Err(reject(PolicyViolation::TransactionHash {
+            sent,
+            computed: transaction_hash,
+        }));
+    }
+
+    let block_hash = state
+        .policy
+        .check(
+            &payload.transaction,
+            payload.chain_id,
+            payload.attestation.as_ref(),
+        )
+        .map_err(reject)?;
+
+    let request = SigningRequest {
+        sender_address: payload.transaction.sender_address,
+        nonce: payload.transaction.nonce,
+        transaction_hash,
+        block_hash,
+        epoch_id: payload.attestation.as_ref().map(|context| context.epoch_id),
+    };
+
+    // Hold the lock until the signature is recorded so that concurrent
+    // requests cannot both pass the check.
+    let mut protection_db = state.protection_db.lock().await;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    if let Some(signature) = protection_db.check(&request, now).map_err(reject)? {
+        tracing::info!(?transaction_hash, "Returning previously signed attestation");
+        return Ok(Json(SignHashResponse { signature }));
+    }
+
+    let signature = state
+        .signing_key
+        .sign(&transaction_hash)
+        .map_err(|error| internal_error("SIGNING_FAILED", error))?;
+    let signature = [signature.r, signature.s];
+    protection_db
+        .record(&request, signature, now)
+        .map_err(|error| internal_error("PROTECTION_DB_ERROR", error))?;
+    tracing::info!(?transaction_hash, ?block_hash, "Signed attestation");
+
+    Ok(Json(SignHashResponse { signature }))
+}
+
+fn reject(violation: PolicyViolation) -> Response {
+    tracing::warn!(code = violation.code(), %violation, "Refusing to sign");
+    violation.into_response()
+}
+
+fn internal_error(code: &'static str, error: impl std::fmt::Display) -> Response {
+    tracing::error!(code, %error, "Internal error");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(code, error)),
+    )
+        .into_response()
+}
@@ -0,0 +1,360 @@
+use axum::{Json, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use starknet_rust::core::types::{BroadcastedInvokeTransactionV3, DataAvailabilityMode, Felt};
+use starknet_rust::macros::selector;
+
+use crate::AttestationContext;
+
+const SELECTOR_ATTEST: Felt = selector!("attest");
+
+/// The transactions this signer is willing to sign.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub chain_id: Felt,
+    pub sender_address: Felt,
+    pub attestation_contract_address: Felt,
+    /// Maximum fee in FRI, including the tip.
+    pub max_fee: u128,
+    pub max_tip: u64,
+}
+
+/// Reasons for refusing to sign a request.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum PolicyViolation {
+    #[error("Unexpected chain ID {0:#x}")]
+    ChainId(Felt),
+    #[error("Unexpected sender address {0:#x}")]
+    SenderAddress(Felt),
+    #[error("Query transactions are not signed")]
+    QueryTransaction,
+    #[error("Unsupported transaction field: {0}")]
+    UnsupportedField(&'static str),
+    #[error("Malformed calldata: {0}")]
+    MalformedCalldata(&'static str),
+    #[error("Expected a single call, got {0}")]
+    CallCount(Felt),
+    #[error("Call to unexpected contract {0:#x}")]
+    ContractAddress(Felt),
+    #[error("Call to unexpected entry point {0:#x}")]
+    Selector(Felt),
+    #[error("Tip {tip} exceeds the maximum of {max_tip}")]
+    TipTooHigh { tip: u64, max_tip: u64 },
+    #[error("Maximum fee {max_fee} exceeds the limit of {limit}")]
+    FeeTooHigh { max_fee: u128, limit: u128 },
+    #[error("Transaction hash {sent:#x} does not match computed hash {computed:#x}")]
+    TransactionHash { sent: Felt, computed: Felt },
+    #[error("Attestation context does not match the transaction: {0}")]
+    AttestationContext(&'static str),
+    #[error("A different transaction has already been signed with nonce {0:#x}")]
+    NonceAlreadySigned(Felt),
+    #[error("A different attestation has already been signed for epoch {0}")]
+    EpochAlreadySigned(u64),
+    #[error("The maximum number of attestations has already been signed for epoch {0}")]
+    EpochSignatureLimit(u64),
+    #[error("The maximum number of attestations has already been signed for block {0:#x}")]
+    BlockSignatureLimit(Felt),
+    #[error("The maximum number of attestations has already been signed in the last {0} seconds")]
+    SignatureRateLimit(u64),
+}
+
+impl PolicyViolation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ChainId(_) => "CHAIN_ID_MISMATCH",
+            Self::SenderAddress(_) => "SENDER_ADDRESS_MISMATCH",
+            Self::QueryTransaction => "QUERY_TRANSACTION",
+            Self::UnsupportedField(_) => "UNSUPPORTED_FIELD",
+            Self::MalformedCalldata(_) => "MALFORMED_CALLDATA",
+            Self::CallCount(_) => "CALL_COUNT",
+            Self::ContractAddress(_) => "CONTRACT_ADDRESS_MISMATCH",
+            Self::Selector(_) => "SELECTOR_MISMATCH",
+            Self::TipTooHigh { .. } => "TIP_TOO_HIGH",
+            Self::FeeTooHigh { .. } => "FEE_TOO_HIGH",
+            Self::TransactionHash { .. } => "TRANSACTION_HASH_MISMATCH",
+            Self::AttestationContext(_) => "ATTESTATION_CONTEXT_MISMATCH",
+            Self::NonceAlreadySigned(_) => "NONCE_ALREADY_SIGNED",
+            Self::EpochAlreadySigned(_) => "EPOCH_ALREADY_SIGNED",
+            Self::EpochSignatureLimit(_) => "EPOCH_SIGNATURE_LIMIT",
+            Self::BlockSignatureLimit(_) => "BLOCK_SIGNATURE_LIMIT",
+            Self::SignatureRateLimit(_) => "SIGNATURE_RATE_LIMIT",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NonceAlreadySigned(_)
+            | Self::EpochAlreadySigned(_)
+            | Self::EpochSignatureLimit(_)
+            | Self::BlockSignatureLimit(_)
+            | Self::SignatureRateLimit(_) => StatusCode::CONFLICT,
+            _ => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Error body returned to clients, e.g. `{"error": {"code": "TIP_TOO_HIGH", "message": "..."}}`.
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetails,
+}
+
+#[derive(Serialize)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    pub fn new(code: &'static str, message: impl ToString) -> Self {
+        Self {
+            error: ErrorDetails {
+                code,
+                message: message.to_string(),
+            },
+        }
+    }
+}
+
+impl IntoResponse for PolicyViolation {
+    fn into_response(self) -> axum::response::Response {
+        (self.status(), Json(ErrorResponse::new(self.code(), &self))).into_response()
+    }
+}
+
+impl Policy {
+    /// Checks that `transaction` is a single `attest` call allowed by this policy.
+    ///
+    /// Returns the block hash being attested.
+    pub fn check(
+        &self,
+        transaction: &BroadcastedInvokeTransactionV3,
+        chain_id: Felt,
+        context: Option<&AttestationContext>,
+    ) -> Result<Felt, PolicyViolation> {
+        if chain_id != self.chain_id {
+            return Err(PolicyViolation::ChainId(chain_id));
+        }
+        if transaction.sender_address != self.sender_address {
+            return Err(PolicyViolation::SenderAddress(transaction.sender_address));
+        }
+        if transaction.is_query {
+            return Err(PolicyViolation::QueryTransaction);
+        }
+        if !transaction.paymaster_data.is_empty() {
+            return Err(PolicyViolation::UnsupportedField("paymaster_data"));
+        }
+        if !transaction.account_deployment_data.is_empty() {
+            return Err(PolicyViolation::UnsupportedField("account_deployment_data"));
+        }
+        if transaction.nonce_data_availability_mode != DataAvailabilityMode::L1
+            || transaction.fee_data_availability_mode != DataAvailabilityMode::L1
+        {
+            return Err(PolicyViolation::UnsupportedField("data_availability_mode"));
+        }
+
+        let block_hash = self.check_calldata(&transaction.calldata)?;
+
+        if transaction.tip > self.max_tip {
+            return Err(PolicyViolation::TipTooHigh {
+                tip: transaction.tip,
+                max_tip: self.max_tip,
+            });
+        }
+        let max_fee = max_fee(transaction);
+        if max_fee > self.max_fee {
+            return Err(PolicyViolation::FeeTooHigh {
+                max_fee,
+                limit: self.max_fee,
+            });
+        }
+
+        if let Some(context) = context {
+            if context.attestation_contract_address != self.attestation_contract_address {
+                return Err(PolicyViolation::AttestationContext(
+                    "attestation_contract_address",
+                ));
+            }
+            if context.block_hash != block_hash {
+                return Err(PolicyViolation::AttestationContext("block_hash"));
+            }
+        }
+
+        Ok(block_hash)
+    }
+
+    /// Decodes `__execute__` calldata: `[call_count, (to, selector, calldata_len, calldata...)...]`.
+    fn check_calldata(&self, calldata: &[Felt]) -> Result<Felt, PolicyViolation> {
+        let [call_count, to, selector, calldata_len, rest @ ..] = calldata else {
+            return Err(PolicyViolation::MalformedCalldata("too short"));
+        };
+        if *call_count != Felt::ONE {
+            return Err(PolicyViolation::CallCount(*call_count));
+        }
+        if *to != self.attestation_contract_address {
+            return Err(PolicyViolation::ContractAddress(*to));
+        }
+        if *selector != SELECTOR_ATTEST {
+            return Err(PolicyViolation::Selector(*selector));
+        }
+        if *calldata_len != Felt::from(rest.len()) {
+            return Err(PolicyViolation::MalformedCalldata(
+                "calldata length mismatch",
+            ));
+        }
+        let [block_hash] = rest else {
+            return Err(PolicyViolation::MalformedCalldata(
+                "attest takes a single argument",
+            ));
+        };
+
+        Ok(*block_hash)
+    }
+}
+
+/// The maximum amount of FRI the transaction can be charged.
+fn max_fee(transaction: &BroadcastedInvokeTransactionV3) -> u128 {
+    let bounds = &transaction.resource_bounds;
+    let resource_fee = |amount: u64, price: u128| u128::from(amount).saturating_mul(price);
+
+    resource_fee(bounds.l1_gas.max_amount, bounds.l1_gas.max_price_per_unit)
+        .saturating_add(resource_fee(
+            bounds.l1_data_gas.max_amount,
+            bounds.l1_data_gas.max_price_per_unit,
+        ))
+        .saturating_add(resource_fee(
+            bounds.l2_gas.max_amount,
+            bounds.l2_gas.max_price_per_unit,
+        ))
+        .saturating_add(resource_fee(
+            bounds.l2_gas.max_amount,
+            u128::from(transaction.tip),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::core::{
+        chain_id,
+        types::{
+            BroadcastedInvokeTransactionV3, DataAvailabilityMode, ResourceBounds,
+            ResourceBoundsMapping,
+        },
+    };
+    use starknet_rust::macros::felt;
+
+    use super::*;
+
+    const SENDER_ADDRESS: Felt =
+        felt!("0x2e216b191ac966ba1d35cb6cfddfaf9c12aec4dfe869d9fa6233611bb334ee9");
+    const ATTESTATION_CONTRACT_ADDRESS: Felt =
+        felt!("0x3f32e152b9637c31bfcf73e434f78591067a01ba070505ff6ee195642c9acfb");
+    const BLOCK_HASH: Felt =
+        felt!("0x7979a0a0a175d7e738e8e9ba6fa6d48f680d67758f719390eee58e790819836");
+
+    fn policy() -> Policy {
+        Policy {
+            chain_id: chain_id::SEPOLIA,
+            sender_address: SENDER_ADDRESS,
+            attestation_contract_address: ATTESTATION_CONTRACT_ADDRESS,
+            max_fee: 10u128.pow(18),
+            max_tip: 1_000_000,
+        }
+    }
+
+    fn transaction() -> BroadcastedInvokeTransactionV3 {
+        BroadcastedInvokeTransactionV3 {
+            sender_address: SENDER_ADDRESS,
+            calldata: vec![
+                Felt::ONE,
+                ATTESTATION_CONTRACT_ADDRESS,
+                SELECTOR_ATTEST,
+                Felt::ONE,
+                BLOCK_HASH,
+            ],
+            signature: vec![],
+            nonce: felt!("0x106"),
+            resource_bounds: ResourceBoundsMapping {
+                l1_gas: ResourceBounds {
+                    max_amount: 0,
+                    max_price_per_unit: 0x51066a69ad72c,
+                },
+                l1_data_gas: ResourceBounds {
+                    max_amount: 0x600,
+                    max_price_per_unit: 0x1254,
+                },
+                l2_gas: ResourceBounds {
+                    max_amount: 0xf00000,
+                    max_price_per_unit: 0x308c5bff6,
+                },
+            },
+            tip: 0,
+            paymaster_data: vec![],
+            account_deployment_data: vec![],
+            nonce_data_availability_mode: DataAvailabilityMode::L1,
+            fee_data_availability_mode: DataAvailabilityMode::L1,
+            is_query: false,
+        }
+    }
+
+    #[test]
+    fn allows_attest_call() {
+        assert_eq!(
+            policy().check(&transaction(), chain_id::SEPOLIA, None),
+            Ok(BLOCK_HASH)
+        );
+    }
+
+    #[test]
+    fn rejects_other_chain() {
+        assert_eq!(
+            policy().check(&transaction(), chain_id::MAINNET, None),
+            Err(PolicyViolation::ChainId(chain_id::MAINNET))
+        );
+    }
+
+    #[test]
+    fn rejects_other_calls() {
+        let mut tx = transaction();
+        tx.calldata[2] = selector!("transfer");
+        assert_eq!(
+            policy().check(&tx, chain_id::SEPOLIA, None),
+            Err(PolicyViolation::Selector(selector!("transfer")))
+        );
+
+        let mut tx = transaction();
+        tx.calldata[0] = Felt::TWO;
+        assert_eq!(
+            policy().check(&tx, chain_id::SEPOLIA, None),
+            Err(PolicyViolation::CallCount(Felt::TWO))
+        );
+
+        let mut tx = transaction();
+        tx.calldata.push(BLOCK_HASH);
+        assert_eq!(
+            policy().check(&tx, chain_id::SEPOLIA, None),
+            Err(PolicyViolation::MalformedCalldata(
+                "calldata length mismatch"
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_expensive_transactions() {
+        let mut tx = transaction();
+        tx.tip = 1_000_001;
+        assert_eq!(
+            policy().check(&tx, chain_id::SEPOLIA, None),
+            Err(PolicyViolation::TipTooHigh {
+                tip: 1_000_001,
+                max_tip: 1_000_000
+            })
+        );
+
+        let mut tx = transaction();
+        tx.resource_bounds.l2_gas.max_price_per_unit = 10u128.pow(18);
+        assert_matches::assert_matches!(
+            policy().check(&tx, chain_id::SEPOLIA, None),
+            Err(PolicyViolation::FeeTooHigh { .. })
+        );
+    }
+}
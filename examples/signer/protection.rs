@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use starknet_rust::core::types::Felt;
+
+use crate::policy::PolicyViolation;
+
+/// Persistent record of signed attestations, guarding against signing
+/// conflicting attestations for the same nonce, block or epoch.
+///
+/// Attestations are limited per attested block even when the client does not
+/// send the epoch: each epoch has a single block to attest. As neither the
+/// block hash nor the epoch sent by the client can be verified, attestations
+/// are also limited per sender within a time window shorter than an epoch, so
+/// that a compromised client cannot drain the fees of the account.
+///
+/// The records are stored as JSON and rewritten atomically after each new
+/// signature, before the signature is returned to the client.
+pub struct ProtectionDb {
+    path: PathBuf,
+    max_signatures_per_epoch: usize,
+    /// In seconds.
+    signature_window: u64,
+    records: Records,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Records {
+    nonces: Vec<NonceRecord>,
+    epochs: Vec<EpochRecord>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct NonceRecord {
+    sender_address: Felt,
+    nonce: Felt,
+    transaction_hash: Felt,
+    signature: [Felt; 2],
+    /// Seconds since the Unix epoch, 0 in records written before it was kept.
+    #[serde(default)]
+    signed_at: u64,
+}
+
+/// The attestations signed for an attested block.
+#[derive(Clone, Serialize, Deserialize)]
+struct EpochRecord {
+    sender_address: Felt,
+    /// Unknown until a v2 client sends it.
+    epoch_id: Option<u64>,
+    block_hash: Felt,
+    nonces: Vec<Felt>,
+}
+
+/// A request to sign an attestation.
+pub struct SigningRequest {
+    pub sender_address: Felt,
+    pub nonce: Felt,
+    pub transaction_hash: Felt,
+    pub block_hash: Felt,
+    /// Only known if the client uses the v2 protocol.
+    pub epoch_id: Option<u64>,
+}
+
+impl ProtectionDb {
+    pub fn open(
+        path: &Path,
+        max_signatures_per_epoch: usize,
+        signature_window: u64,
+    ) -> anyhow::Result<Self> {
+        let records = match std::fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("Parsing protection database {}", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Records::default(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Reading protection database {}", path.display()));
+            }
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            max_signatures_per_epoch,
+            signature_window,
+            records,
+        })
+    }
+
+    /// Checks whether `request` may be signed at `now` (seconds since the Unix
+    /// epoch).
+    ///
+    /// Returns the previously returned signature if the very same transaction
+    /// has already been signed.
+    pub fn check(
+        &self,
+        request: &SigningRequest,
+        now: u64,
+    ) -> Result<Option<[Felt; 2]>, PolicyViolation> {
+        if let Some(record) = self.records.nonces.iter().find(|record| {
+            record.sender_address == request.sender_address && record.nonce == request.nonce
+        }) {
+            return if record.transaction_hash == request.transaction_hash {
+                Ok(Some(record.signature))
+            } else {
+                Err(PolicyViolation::NonceAlreadySigned(request.nonce))
+            };
+        }
+
+        for record in self
+            .records
+            .epochs
+            .iter()
+            .filter(|record| record.sender_address == request.sender_address)
+        {
+            if record.block_hash == request.block_hash {
+                if let (Some(recorded), Some(requested)) = (record.epoch_id, request.epoch_id)
+                    && recorded != requested
+                {
+                    // The same block cannot be assigned to two epochs.
+                    return Err(PolicyViolation::EpochAlreadySigned(recorded));
+                }
+                if record.nonces.len() >= self.max_signatures_per_epoch {
+                    return Err(match request.epoch_id.or(record.epoch_id) {
+                        Some(epoch_id) => PolicyViolation::EpochSignatureLimit(epoch_id),
+                        None => PolicyViolation::BlockSignatureLimit(request.block_hash),
+                    });
+                }
+            } else if let Some(epoch_id) = request.epoch_id
+                && record.epoch_id == Some(epoch_id)
+            {
+                return Err(PolicyViolation::EpochAlreadySigned(epoch_id));
+            }
+        }
+
+        let window_start = now.saturating_sub(self.signature_window);
+        let recent = self
+            .records
+            .nonces
+            .iter()
+            .filter(|record| {
+                record.sender_address == request.sender_address && record.signed_at > window_start
+            })
+            .count();
+        if recent >= self.max_signatures_per_epoch {
+            return Err(PolicyViolation::SignatureRateLimit(self.signature_window));
+        }
+
+        Ok(None)
+    }
+
+    /// Records a new signature signed at `now` and persists the database. The
+    /// record is only kept if it could be persisted.
+    pub fn record(
+        &mut self,
+        request: &SigningRequest,
+        signature: [Felt; 2],
+        now: u64,
+    ) -> anyhow::Result<()> {
+        let mut records = self.records.clone();
+        records.nonces.push(NonceRecord {
+            sender_address: request.sender_address,
+            nonce: request.nonce,
+            transaction_hash: request.transaction_hash,
+            signature,
+            signed_at: now,
+        });
+
+        match records.epochs.iter_mut().find(|record| {
+            record.sender_address == request.sender_address
+                && record.block_hash == request.block_hash
+        }) {
+            Some(record) => {
+                record.nonces.push(request.nonce);
+                record.epoch_id = record.epoch_id.or(request.epoch_id);
+            }
+            None => records.epochs.push(EpochRecord {
+                sender_address: request.sender_address,
+                epoch_id: request.epoch_id,
+                block_hash: request.block_hash,
+                nonces: vec![request.nonce],
+            }),
+        }
+
+        self.persist(&records)?;
+        self.records = records;
+        Ok(())
+    }
+
+    fn persist(&self, records: &Records) -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let contents = serde_json::to_vec_pretty(records)?;
+        let temporary_path = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temporary_path)
+            .with_context(|| format!("Creating {}", temporary_path.display()))?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, &self.path)
+            .with_context(|| format!("Writing protection database {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::macros::felt;
+
+    use super::*;
+
+    const SENDER_ADDRESS: Felt = felt!("0xfeedbeef");
+    const BLOCK_HASH: Felt = felt!("0x123456789abcdef");
+    const SIGNATURE: [Felt; 2] = [felt!("0x1"), felt!("0x2")];
+    const WINDOW: u64 = 600;
+    const NOW: u64 = 1_000_000;
+    const LATER: u64 = NOW + WINDOW;
+
+    struct TemporaryPath(PathBuf);
+
+    impl TemporaryPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "signer-protection-{name}-{}.json",
+                std::process::id()
+            )))
+        }
+    }
+
+    impl Drop for TemporaryPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn request(
+        nonce: u64,
+        transaction_hash: u64,
+        epoch_id: u64,
+        block_hash: Felt,
+    ) -> SigningRequest {
+        SigningRequest {
+            sender_address: SENDER_ADDRESS,
+            nonce: nonce.into(),
+            transaction_hash: transaction_hash.into(),
+            block_hash,
+            epoch_id: Some(epoch_id),
+        }
+    }
+
+    #[test]
+    fn identical_request_returns_cached_signature() {
+        let path = TemporaryPath::new("cached");
+        let mut db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        let first = request(1, 100, 10, BLOCK_HASH);
+
+        assert_eq!(db.check(&first, NOW), Ok(None));
+        db.record(&first, SIGNATURE, NOW).unwrap();
+
+        // Also after re-opening the database.
+        let db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        assert_eq!(db.check(&first, NOW), Ok(Some(SIGNATURE)));
+    }
+
+    #[test]
+    fn conflicting_requests_are_refused() {
+        let path = TemporaryPath::new("conflicts");
+        let mut db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        db.record(&request(1, 100, 10, BLOCK_HASH), SIGNATURE, NOW)
+            .unwrap();
+
+        // Different transaction with the same nonce.
+        assert_eq!(
+            db.check(&request(1, 101, 10, BLOCK_HASH), NOW),
+            Err(PolicyViolation::NonceAlreadySigned(Felt::ONE))
+        );
+        // Different block hash in the same epoch.
+        assert_eq!(
+            db.check(&request(2, 102, 10, felt!("0x1")), NOW),
+            Err(PolicyViolation::EpochAlreadySigned(10))
+        );
+        // Second attestation in the same epoch.
+        assert_eq!(
+            db.check(&request(2, 102, 10, BLOCK_HASH), NOW),
+            Err(PolicyViolation::EpochSignatureLimit(10))
+        );
+        // Same block hash claimed for another epoch.
+        assert_eq!(
+            db.check(&request(2, 102, 11, BLOCK_HASH), NOW),
+            Err(PolicyViolation::EpochAlreadySigned(10))
+        );
+        // Next epoch.
+        assert_eq!(
+            db.check(&request(2, 102, 11, felt!("0x1")), LATER),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn requests_without_epoch_are_limited_per_block() {
+        let path = TemporaryPath::new("without-epoch");
+        let mut db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        let without_epoch = |nonce, transaction_hash, block_hash| SigningRequest {
+            epoch_id: None,
+            ..request(nonce, transaction_hash, 0, block_hash)
+        };
+        db.record(&without_epoch(1, 100, BLOCK_HASH), SIGNATURE, NOW)
+            .unwrap();
+
+        // Fresh nonce for the same block.
+        assert_eq!(
+            db.check(&without_epoch(2, 102, BLOCK_HASH), NOW),
+            Err(PolicyViolation::BlockSignatureLimit(BLOCK_HASH))
+        );
+        // Also when the client switches to the v2 protocol.
+        assert_eq!(
+            db.check(&request(2, 102, 10, BLOCK_HASH), NOW),
+            Err(PolicyViolation::EpochSignatureLimit(10))
+        );
+        // Next block.
+        assert_eq!(
+            db.check(&without_epoch(2, 102, felt!("0x1")), LATER),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn retries_within_epoch_limit_are_allowed() {
+        let path = TemporaryPath::new("retries");
+        let mut db = ProtectionDb::open(&path.0, 2, WINDOW).unwrap();
+        db.record(&request(1, 100, 10, BLOCK_HASH), SIGNATURE, NOW)
+            .unwrap();
+
+        assert_eq!(db.check(&request(2, 102, 10, BLOCK_HASH), NOW), Ok(None));
+    }
+
+    #[test]
+    fn attestations_are_rate_limited_whatever_the_block_and_epoch() {
+        let path = TemporaryPath::new("rate-limit");
+        let mut db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        let without_epoch = |nonce, transaction_hash, block_hash| SigningRequest {
+            epoch_id: None,
+            ..request(nonce, transaction_hash, 0, block_hash)
+        };
+        db.record(&without_epoch(1, 100, BLOCK_HASH), SIGNATURE, NOW)
+            .unwrap();
+
+        // A new block hash, or a made up epoch, within the window.
+        assert_eq!(
+            db.check(&without_epoch(2, 102, felt!("0x1")), LATER - 1),
+            Err(PolicyViolation::SignatureRateLimit(WINDOW))
+        );
+        assert_eq!(
+            db.check(&request(2, 102, 11, felt!("0x1")), LATER - 1),
+            Err(PolicyViolation::SignatureRateLimit(WINDOW))
+        );
+        // Retrying the signed request still returns its signature.
+        assert_eq!(
+            db.check(&without_epoch(1, 100, BLOCK_HASH), LATER - 1),
+            Ok(Some(SIGNATURE))
+        );
+    }
+
+    #[test]
+    fn unpersisted_signatures_are_not_kept() {
+        let path = TemporaryPath::new("unpersisted");
+        let mut db = ProtectionDb::open(&path.0, 1, WINDOW).unwrap();
+        // The temporary file cannot be created in a missing directory.
+        db.path = path.0.join("missing").join("db.json");
+        let first = request(1, 100, 10, BLOCK_HASH);
+
+        assert!(db.record(&first, SIGNATURE, NOW).is_err());
+        assert_eq!(db.check(&first, NOW), Ok(None));
+    }
+}
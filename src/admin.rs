@@ -0,0 +1,164 @@
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::secret::Secret;
+
+/// An operator action requested on the admin API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Stop sending attestation transactions, but keep tracking epochs.
+    Pause,
+    Resume,
+    /// Re-initialize the state from the attestation info of the staking contract.
+    Reinitialize,
+    /// Handle the latest block header again now, instead of waiting for the next one.
+    Retry,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::Reinitialize => "reinitialize",
+            Action::Retry => "retry",
+        }
+    }
+}
+
+/// An action for the main loop to perform, and where to send its result.
+pub struct Request {
+    pub action: Action,
+    pub reply: oneshot::Sender<anyhow::Result<()>>,
+}
+
+#[derive(Clone)]
+struct AdminState {
+    token: Secret,
+    requests: mpsc::Sender<Request>,
+}
+
+/// The admin endpoints, requiring `Authorization: Bearer <token>`.
+pub fn router(token: Secret, requests: mpsc::Sender<Request>) -> axum::Router {
+    axum::Router::new()
+        .route(
+            "/admin/pause",
+            axum::routing::post(|state, headers| action_route(state, headers, Action::Pause)),
+        )
+        .route(
+            "/admin/resume",
+            axum::routing::post(|state, headers| action_route(state, headers, Action::Resume)),
+        )
+        .route(
+            "/admin/reinitialize",
+            axum::routing::post(|state, headers| {
+                action_route(state, headers, Action::Reinitialize)
+            }),
+        )
+        .route(
+            "/admin/retry",
+            axum::routing::post(|state, headers| action_route(state, headers, Action::Retry)),
+        )
+        .with_state(AdminState { token, requests })
+}
+
+async fn action_route(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    action: Action,
+) -> Response {
+    if !authorized(&state.token, &headers) {
+        tracing::warn!(action=%action.as_str(), "Unauthorized admin request");
+        record_action(action, "unauthorized");
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+
+    tracing::info!(action=%action.as_str(), "Admin action requested");
+    let (reply, result) = oneshot::channel();
+    if state
+        .requests
+        .send(Request { action, reply })
+        .await
+        .is_err()
+    {
+        return shutting_down(action);
+    }
+    match result.await {
+        Ok(Ok(())) => {
+            tracing::info!(action=%action.as_str(), "Admin action done");
+            record_action(action, "ok");
+            Json(serde_json::json!({ "action": action.as_str() })).into_response()
+        }
+        Ok(Err(error)) => {
+            tracing::error!(action=%action.as_str(), ?error, "Admin action failed");
+            record_action(action, "error");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "action": action.as_str(),
+                    "error": format!("{error:#}"),
+                })),
+            )
+                .into_response()
+        }
+        Err(_) => shutting_down(action),
+    }
+}
+
+fn shutting_down(action: Action) -> Response {
+    tracing::warn!(action=%action.as_str(), "Admin action not done, shutting down");
+    record_action(action, "unavailable");
+    (StatusCode::SERVICE_UNAVAILABLE, "Shutting down").into_response()
+}
+
+fn record_action(action: Action, result: &'static str) {
+    metrics::counter!(
+        "validator_attestation_admin_action_count",
+        "action" => action.as_str(),
+        "result" => result
+    )
+    .increment(1);
+}
+
+fn authorized(token: &Secret, headers: &HeaderMap) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.expose().as_bytes()))
+}
+
+/// Compares without returning early, not to leak the token through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn authorization() {
+        let token = Secret::new("s3cret");
+        let mut headers = HeaderMap::new();
+        assert!(!authorized(&token, &headers));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cret"),
+        );
+        assert!(authorized(&token, &headers));
+
+        for value in ["Bearer s3cre", "Bearer s3cret2", "s3cret", "Basic s3cret"] {
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_static(value));
+            assert!(!authorized(&token, &headers), "{value}");
+        }
+    }
+}
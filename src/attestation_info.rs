@@ -13,6 +13,24 @@ pub struct AttestationInfo {
 }
 
 impl AttestationInfo {
+    /// The attestation info of another epoch, assuming the same stake and
+    /// epoch length. `None` if the epoch would start before genesis.
+    pub fn for_epoch(&self, epoch_id: u64) -> Option<AttestationInfo> {
+        Some(AttestationInfo {
+            epoch_id,
+            current_epoch_starting_block: self.epochs().starting_block(epoch_id)?,
+            ..self.clone()
+        })
+    }
+
+    pub fn epochs(&self) -> Epochs {
+        Epochs {
+            epoch_id: self.epoch_id,
+            starting_block: self.current_epoch_starting_block,
+            epoch_len: self.epoch_len,
+        }
+    }
+
     pub fn calculate_expected_attestation_block(&self) -> u64 {
         let mut h = PoseidonHasher::new();
         h.update(self.stake.into());
@@ -31,3 +49,36 @@ impl AttestationInfo {
         self.current_epoch_starting_block + block_offset
     }
 }
+
+/// Epochs of constant length, anchored at a known epoch.
+#[derive(Clone, Copy, Debug)]
+pub struct Epochs {
+    pub epoch_id: u64,
+    pub starting_block: u64,
+    pub epoch_len: u64,
+}
+
+impl Epochs {
+    /// The first block of `epoch_id`, if the epoch starts after genesis.
+    pub fn starting_block(&self, epoch_id: u64) -> Option<u64> {
+        let offset = (epoch_id as i128 - self.epoch_id as i128) * self.epoch_len as i128;
+        (self.starting_block as i128 + offset).try_into().ok()
+    }
+
+    /// The epochs entirely within the blocks `from_block..=to_block`, as
+    /// `(epoch_id, first_block)`.
+    pub fn within(&self, from_block: u64, to_block: u64) -> impl Iterator<Item = (u64, u64)> {
+        let epoch_len = self.epoch_len as i128;
+        let offset = from_block as i128 - self.starting_block as i128;
+        // The first epoch starting at or after `from_block`.
+        let first_epoch_id = (self.epoch_id as i128 - (-offset).div_euclid(epoch_len)).max(0);
+        let starting_block = self.starting_block as i128;
+        let anchor = self.epoch_id as i128;
+
+        (first_epoch_id..)
+            .map(move |epoch_id| (epoch_id, starting_block + (epoch_id - anchor) * epoch_len))
+            .skip_while(|(_, first_block)| *first_block < 0)
+            .take_while(move |(_, first_block)| first_block + epoch_len - 1 <= to_block as i128)
+            .map(|(epoch_id, first_block)| (epoch_id as u64, first_block as u64))
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::Serialize;
+use starknet_rust::{
+    core::types::{BlockId, EmittedEvent, EventFilter, Felt},
+    providers::Provider,
+};
+
+use crate::{
+    attestation_info::Epochs,
+    events::{self, AttestationEvent},
+    output::Row,
+};
+
+/// Number of events requested per `starknet_getEvents` call.
+const CHUNK_SIZE: u64 = 1000;
+
+/// Whether the staker attested in an epoch, reconstructed from events.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EpochAttestation {
+    pub epoch_id: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub attested: bool,
+    /// The block and transaction of the attestation, if attested.
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<String>,
+}
+
+impl Row for EpochAttestation {
+    const HEADER: &'static [&'static str] = &[
+        "epoch_id",
+        "first_block",
+        "last_block",
+        "attested",
+        "block_number",
+        "transaction_hash",
+    ];
+
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.epoch_id.to_string(),
+            self.first_block.to_string(),
+            self.last_block.to_string(),
+            self.attested.to_string(),
+            self.block_number
+                .map(|block_number| block_number.to_string())
+                .unwrap_or_default(),
+            self.transaction_hash.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Fetches the `StakerAttestationSuccessful` events of `staker_address` in
+/// `from_block..=to_block`.
+pub async fn fetch_attestation_events<P: Provider + Sync>(
+    provider: &P,
+    attestation_contract_address: Felt,
+    staker_address: Felt,
+    from_block: u64,
+    to_block: u64,
+) -> anyhow::Result<Vec<EmittedEvent>> {
+    let filter = EventFilter {
+        from_block: Some(BlockId::Number(from_block)),
+        to_block: Some(BlockId::Number(to_block)),
+        address: Some(attestation_contract_address),
+        keys: Some(vec![
+            vec![events::SELECTOR_STAKER_ATTESTATION_SUCCESSFUL],
+            vec![staker_address],
+        ]),
+    };
+
+    let mut events = Vec::new();
+    let mut continuation_token = None;
+    loop {
+        let page = provider
+            .get_events(filter.clone(), continuation_token, CHUNK_SIZE)
+            .await
+            .context("Fetching attestation events")?;
+        tracing::debug!(count=%page.events.len(), "Fetched attestation events");
+        events.extend(page.events);
+        continuation_token = page.continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(events)
+}
+
+/// Reconstructs which of the epochs within `from_block..=to_block` the staker
+/// attested, assuming their length has not changed.
+pub fn reconstruct(
+    epochs: Epochs,
+    staker_address: Felt,
+    from_block: u64,
+    to_block: u64,
+    events: &[EmittedEvent],
+) -> Vec<EpochAttestation> {
+    let mut attestations = HashMap::new();
+    for event in events {
+        match events::parse_staker_attestation_successful(event) {
+            Ok(AttestationEvent::StakerAttestationSuccessful {
+                staker_address: event_staker_address,
+                epoch_id,
+            }) if event_staker_address == staker_address => {
+                attestations.entry(epoch_id).or_insert(event);
+            }
+            Ok(_) => {}
+            Err(error) => tracing::debug!(%error, "Failed to parse event"),
+        }
+    }
+
+    epochs
+        .within(from_block, to_block)
+        .map(|(epoch_id, first_block)| {
+            let event = attestations.get(&epoch_id);
+            EpochAttestation {
+                epoch_id,
+                first_block,
+                last_block: first_block + epochs.epoch_len - 1,
+                attested: event.is_some(),
+                block_number: event.and_then(|event| event.block_number),
+                transaction_hash: event.map(|event| format!("{:#x}", event.transaction_hash)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::macros::felt;
+
+    use super::*;
+
+    const STAKER_ADDRESS: Felt = felt!("0xdeadbeef");
+
+    const EPOCHS: Epochs = Epochs {
+        epoch_id: 10,
+        starting_block: 1000,
+        epoch_len: 100,
+    };
+
+    fn event(staker_address: Felt, epoch_id: u64, block_number: u64) -> EmittedEvent {
+        EmittedEvent {
+            from_address: felt!("0xabcdef"),
+            keys: vec![
+                events::SELECTOR_STAKER_ATTESTATION_SUCCESSFUL,
+                staker_address,
+            ],
+            data: vec![epoch_id.into()],
+            block_hash: None,
+            block_number: Some(block_number),
+            transaction_hash: felt!("0x1234"),
+            transaction_index: 0,
+            event_index: 0,
+        }
+    }
+
+    #[test]
+    fn epochs_within_block_range() {
+        assert_eq!(
+            EPOCHS.within(750, 1099).collect::<Vec<_>>(),
+            vec![(8, 800), (9, 900), (10, 1000)]
+        );
+        assert_eq!(
+            EPOCHS.within(800, 1198).collect::<Vec<_>>(),
+            vec![(8, 800), (9, 900), (10, 1000)]
+        );
+        assert_eq!(EPOCHS.within(1201, 1299).count(), 0);
+        // No epochs before the first one.
+        let epochs = Epochs {
+            starting_block: 1050,
+            ..EPOCHS
+        };
+        assert_eq!(
+            epochs.within(0, 249).collect::<Vec<_>>(),
+            vec![(0, 50), (1, 150)]
+        );
+    }
+
+    #[test]
+    fn missed_epochs() {
+        let events = [
+            event(STAKER_ADDRESS, 8, 820),
+            event(felt!("0x1"), 9, 920),
+            event(STAKER_ADDRESS, 10, 1020),
+        ];
+
+        let attestations = reconstruct(EPOCHS, STAKER_ADDRESS, 800, 1099, &events);
+
+        assert_eq!(
+            attestations
+                .iter()
+                .map(|attestation| (attestation.epoch_id, attestation.attested))
+                .collect::<Vec<_>>(),
+            vec![(8, true), (9, false), (10, true)]
+        );
+        assert_eq!(attestations[0].block_number, Some(820));
+        assert_eq!(attestations[0].last_block, 899);
+        assert_eq!(attestations[1].transaction_hash, None);
+    }
+}
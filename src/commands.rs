@@ -0,0 +1,717 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::Serialize;
+use starknet_rust::{
+    core::types::Felt,
+    providers::{JsonRpcClient, Provider, jsonrpc::HttpTransport},
+};
+use url::Url;
+
+use crate::{
+    attestation_info::AttestationInfo,
+    backfill,
+    history::{EpochRecord, History},
+    jsonrpc::{Client, StarknetRpcClient},
+    network::{ContractAddresses, NetworkProfile},
+    output::{self, OutputFormat, Row},
+    rpc_version,
+};
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// List recent epochs from the attestation history.
+    History(HistoryArgs),
+    /// Summarize attestation performance over a date range.
+    Report(ReportArgs),
+    /// Reconstruct past attestations of the staker from events on chain.
+    Backfill(BackfillArgs),
+    /// Calculate the blocks to attest and attestation windows of epochs.
+    Schedule(ScheduleArgs),
+}
+
+#[derive(clap::Args)]
+pub struct HistoryArgs {
+    #[arg(
+        long,
+        long_help = "The attestation history database written by --history-database.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_HISTORY_DATABASE"
+    )]
+    pub history_database: PathBuf,
+
+    #[arg(
+        long,
+        long_help = "The number of epochs to list.",
+        default_value = "20",
+        value_name = "COUNT"
+    )]
+    pub limit: usize,
+
+    #[arg(long, default_value = "table", value_name = "FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+pub struct ReportArgs {
+    #[arg(
+        long,
+        long_help = "The attestation history database written by --history-database.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_HISTORY_DATABASE"
+    )]
+    pub history_database: PathBuf,
+
+    #[arg(
+        long,
+        long_help = "The first day (UTC) of the report. Defaults to 30 days before --to.",
+        value_name = "YYYY-MM-DD"
+    )]
+    pub from: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        long_help = "The last day (UTC) of the report. Defaults to today.",
+        value_name = "YYYY-MM-DD"
+    )]
+    pub to: Option<NaiveDate>,
+
+    #[arg(long, default_value = "table", value_name = "FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+pub struct BackfillArgs {
+    #[arg(
+        long,
+        long_help = "The address of the staker's operational account.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_STAKER_OPERATIONAL_ADDRESS"
+    )]
+    pub staker_operational_address: Felt,
+
+    #[arg(
+        long,
+        long_help = "The URL of the Starknet node's JSON-RPC endpoint.",
+        value_name = "URL",
+        env = "VALIDATOR_ATTESTATION_STARKNET_NODE_URL"
+    )]
+    pub node_url: Url,
+
+    #[arg(
+        long,
+        long_help = "The address of the staking contract.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_STAKING_CONTRACT_ADDRESS"
+    )]
+    pub staking_contract_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The address of the attestation contract.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_ATTESTATION_CONTRACT_ADDRESS"
+    )]
+    pub attestation_contract_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The first block to search for attestations.",
+        value_name = "BLOCK"
+    )]
+    pub from_block: u64,
+
+    #[arg(
+        long,
+        long_help = "The last block to search for attestations. Defaults to the latest block.",
+        value_name = "BLOCK"
+    )]
+    pub to_block: Option<u64>,
+
+    #[arg(long, default_value = "table", value_name = "FORMAT")]
+    pub format: OutputFormat,
+}
+
+/// Parameters not given on the command line are fetched from the node.
+#[derive(clap::Args)]
+pub struct ScheduleArgs {
+    #[arg(long, long_help = "The address of the staker.", value_name = "ADDRESS")]
+    pub staker_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The stake of the staker in FRI. The current stake is assumed for all epochs.",
+        value_name = "FRI"
+    )]
+    pub stake: Option<u128>,
+
+    #[arg(
+        long,
+        long_help = "The length of epochs in blocks.",
+        value_name = "BLOCKS"
+    )]
+    pub epoch_length: Option<u64>,
+
+    #[arg(
+        long,
+        long_help = "An epoch whose first block is --epoch-starting-block. Epochs are assumed \
+                     to be of the same length before and after it.",
+        value_name = "EPOCH",
+        requires = "epoch_starting_block"
+    )]
+    pub epoch_id: Option<u64>,
+
+    #[arg(
+        long,
+        long_help = "The first block of --epoch-id.",
+        value_name = "BLOCK",
+        requires = "epoch_id"
+    )]
+    pub epoch_starting_block: Option<u64>,
+
+    #[arg(
+        long,
+        long_help = "The length of the attestation window in blocks.",
+        value_name = "BLOCKS"
+    )]
+    pub attestation_window: Option<u16>,
+
+    #[arg(
+        long,
+        long_help = "The first epoch of the schedule. Defaults to the current epoch, or \
+                     --epoch-id.",
+        value_name = "EPOCH"
+    )]
+    pub from_epoch: Option<u64>,
+
+    #[arg(
+        long,
+        long_help = "The number of epochs in the schedule.",
+        default_value = "10",
+        value_name = "COUNT"
+    )]
+    pub count: u64,
+
+    #[arg(
+        long,
+        long_help = "The URL of the Starknet node's JSON-RPC endpoint, used to fetch the \
+                     parameters not given on the command line.",
+        value_name = "URL",
+        env = "VALIDATOR_ATTESTATION_STARKNET_NODE_URL"
+    )]
+    pub node_url: Option<Url>,
+
+    #[arg(
+        long,
+        long_help = "The address of the staker's operational account, used to fetch the \
+                     parameters not given on the command line.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_STAKER_OPERATIONAL_ADDRESS"
+    )]
+    pub staker_operational_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The address of the staking contract.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_STAKING_CONTRACT_ADDRESS"
+    )]
+    pub staking_contract_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The address of the attestation contract.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_ATTESTATION_CONTRACT_ADDRESS"
+    )]
+    pub attestation_contract_address: Option<Felt>,
+
+    #[arg(long, default_value = "table", value_name = "FORMAT")]
+    pub format: OutputFormat,
+}
+
+impl Command {
+    pub async fn run(self, networks: &[NetworkProfile]) -> anyhow::Result<()> {
+        match self {
+            Command::History(args) => history(args),
+            Command::Report(args) => report(args),
+            Command::Backfill(args) => backfill(args, networks).await,
+            Command::Schedule(args) => schedule(args, networks).await,
+        }
+    }
+}
+
+fn history(args: HistoryArgs) -> anyhow::Result<()> {
+    let history = History::open_read_only(&args.history_database)?;
+    let epochs: Vec<_> = history
+        .recent_epochs(args.limit)?
+        .into_iter()
+        .map(EpochRow::from)
+        .collect();
+    output::write(&mut std::io::stdout().lock(), args.format, &epochs)
+}
+
+fn report(args: ReportArgs) -> anyhow::Result<()> {
+    let to = args.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = args.from.unwrap_or(to - Days::new(30));
+    anyhow::ensure!(from <= to, "--from must not be after --to");
+
+    let history = History::open_read_only(&args.history_database)?;
+    let epochs = history.epochs_between(start_of_day(from), start_of_day(to + Days::new(1)))?;
+    let report = Report::new(from, to, &epochs);
+    output::write(&mut std::io::stdout().lock(), args.format, &[report])
+}
+
+/// A client of the node endpoint, checked to serve the version of the JSON-RPC
+/// API required, as when attesting.
+async fn connect(node_url: &Url) -> anyhow::Result<JsonRpcClient<HttpTransport>> {
+    let http_client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let client = JsonRpcClient::new(HttpTransport::new_with_client(
+        node_url.clone(),
+        http_client,
+    ));
+    rpc_version::check(&client).await?;
+    Ok(client)
+}
+
+/// Fetches the current attestation info of the staker, returning it with the
+/// address of the attestation contract.
+async fn fetch_attestation_info(
+    provider: JsonRpcClient<HttpTransport>,
+    operational_address: Felt,
+    staking_contract_address: Option<Felt>,
+    attestation_contract_address: Option<Felt>,
+    networks: &[NetworkProfile],
+) -> anyhow::Result<(AttestationInfo, Felt)> {
+    let chain_id = provider.chain_id().await.context("Getting chain ID")?;
+    let contract_addresses = ContractAddresses::new(
+        chain_id,
+        networks,
+        staking_contract_address,
+        attestation_contract_address,
+        None,
+    );
+    let attestation_contract_address = contract_addresses.attestation()?;
+    let client = StarknetRpcClient::new(
+        provider,
+        contract_addresses.staking()?,
+        attestation_contract_address,
+        // Balances are not queried.
+        contract_addresses.strk().unwrap_or_default(),
+        Default::default(),
+        None,
+    );
+
+    let attestation_info = client
+        .get_attestation_info(operational_address)
+        .await
+        .context("Getting attestation info")?;
+    Ok((attestation_info, attestation_contract_address))
+}
+
+async fn backfill(args: BackfillArgs, networks: &[NetworkProfile]) -> anyhow::Result<()> {
+    let provider = connect(&args.node_url).await?;
+    let (attestation_info, attestation_contract_address) = fetch_attestation_info(
+        provider.clone(),
+        args.staker_operational_address,
+        args.staking_contract_address,
+        args.attestation_contract_address,
+        networks,
+    )
+    .await?;
+    let to_block = match args.to_block {
+        Some(to_block) => to_block,
+        None => provider
+            .block_number()
+            .await
+            .context("Getting latest block number")?,
+    };
+    anyhow::ensure!(
+        args.from_block <= to_block,
+        "--from-block must not be after --to-block"
+    );
+
+    let events = backfill::fetch_attestation_events(
+        &provider,
+        attestation_contract_address,
+        attestation_info.staker_address,
+        args.from_block,
+        to_block,
+    )
+    .await?;
+    let attestations = backfill::reconstruct(
+        attestation_info.epochs(),
+        attestation_info.staker_address,
+        args.from_block,
+        to_block,
+        &events,
+    );
+    output::write(&mut std::io::stdout().lock(), args.format, &attestations)
+}
+
+async fn schedule(args: ScheduleArgs, networks: &[NetworkProfile]) -> anyhow::Result<()> {
+    let complete = args.staker_address.is_some()
+        && args.stake.is_some()
+        && args.epoch_length.is_some()
+        && args.epoch_id.is_some()
+        && args.attestation_window.is_some();
+    let live = if complete {
+        None
+    } else {
+        let (Some(node_url), Some(operational_address)) =
+            (&args.node_url, args.staker_operational_address)
+        else {
+            anyhow::bail!(
+                "Either give --staker-address, --stake, --epoch-length, --epoch-id, \
+                 --epoch-starting-block and --attestation-window, or --node-url and \
+                 --staker-operational-address to fetch them"
+            );
+        };
+        let (attestation_info, _) = fetch_attestation_info(
+            connect(node_url).await?,
+            operational_address,
+            args.staking_contract_address,
+            args.attestation_contract_address,
+            networks,
+        )
+        .await?;
+        Some(attestation_info)
+    };
+
+    // Parameters given on the command line override the fetched ones.
+    let (epoch_id, starting_block) = match (args.epoch_id, args.epoch_starting_block, &live) {
+        (Some(epoch_id), Some(starting_block), _) => (epoch_id, starting_block),
+        (_, _, Some(live)) => (live.epoch_id, live.current_epoch_starting_block),
+        _ => unreachable!("either complete or fetched"),
+    };
+    let attestation_info = AttestationInfo {
+        staker_address: args
+            .staker_address
+            .or(live.as_ref().map(|live| live.staker_address))
+            .context("Missing staker address")?,
+        operational_address: args.staker_operational_address.unwrap_or_default(),
+        stake: args
+            .stake
+            .or(live.as_ref().map(|live| live.stake))
+            .context("Missing stake")?,
+        epoch_len: args
+            .epoch_length
+            .or(live.as_ref().map(|live| live.epoch_len))
+            .context("Missing epoch length")?,
+        epoch_id,
+        current_epoch_starting_block: starting_block,
+        attestation_window: args
+            .attestation_window
+            .or(live.as_ref().map(|live| live.attestation_window))
+            .context("Missing attestation window")?,
+    };
+    anyhow::ensure!(
+        attestation_info.epoch_len > attestation_info.attestation_window as u64,
+        "The epoch length must be larger than the attestation window"
+    );
+
+    let from_epoch = args.from_epoch.unwrap_or(epoch_id);
+    let schedule = schedule_epochs(&attestation_info, from_epoch..from_epoch + args.count);
+    output::write(&mut std::io::stdout().lock(), args.format, &schedule)
+}
+
+/// The attestation schedule of `epoch_ids`, based on the parameters of the
+/// epoch in `attestation_info`.
+fn schedule_epochs(
+    attestation_info: &AttestationInfo,
+    epoch_ids: std::ops::Range<u64>,
+) -> Vec<ScheduleRow> {
+    epoch_ids
+        .filter_map(|epoch_id| {
+            let epoch_info = attestation_info.for_epoch(epoch_id)?;
+            let block_to_attest = epoch_info.calculate_expected_attestation_block();
+            let window =
+                crate::state::attestation_window(block_to_attest, epoch_info.attestation_window);
+            Some(ScheduleRow {
+                epoch_id,
+                epoch_starting_block: epoch_info.current_epoch_starting_block,
+                block_to_attest,
+                window_start: window.start,
+                window_end: window.end,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ScheduleRow {
+    epoch_id: u64,
+    epoch_starting_block: u64,
+    block_to_attest: u64,
+    window_start: u64,
+    /// The first block after the window.
+    window_end: u64,
+}
+
+impl Row for ScheduleRow {
+    const HEADER: &'static [&'static str] = &[
+        "epoch_id",
+        "epoch_starting_block",
+        "block_to_attest",
+        "window_start",
+        "window_end",
+    ];
+
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.epoch_id.to_string(),
+            self.epoch_starting_block.to_string(),
+            self.block_to_attest.to_string(),
+            self.window_start.to_string(),
+            self.window_end.to_string(),
+        ]
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+fn fri_to_strk(fri: u64) -> f64 {
+    fri as f64 / 1e18
+}
+
+#[derive(Debug, Serialize)]
+struct EpochRow {
+    epoch_id: u64,
+    block_to_attest: u64,
+    block_hash: Option<String>,
+    window_start: u64,
+    window_end: u64,
+    outcome: Option<String>,
+    attempts: u64,
+    fee_strk: f64,
+    failure_reasons: Vec<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<EpochRecord> for EpochRow {
+    fn from(epoch: EpochRecord) -> Self {
+        Self {
+            epoch_id: epoch.epoch_id,
+            block_to_attest: epoch.block_to_attest,
+            block_hash: epoch.block_hash,
+            window_start: epoch.window_start,
+            window_end: epoch.window_end,
+            outcome: epoch.outcome,
+            attempts: epoch.attempts,
+            fee_strk: fri_to_strk(epoch.fee),
+            failure_reasons: epoch.failure_reasons,
+            updated_at: epoch.updated_at,
+        }
+    }
+}
+
+impl Row for EpochRow {
+    const HEADER: &'static [&'static str] = &[
+        "epoch_id",
+        "block_to_attest",
+        "block_hash",
+        "window",
+        "outcome",
+        "attempts",
+        "fee_strk",
+        "failure_reasons",
+        "updated_at",
+    ];
+
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.epoch_id.to_string(),
+            self.block_to_attest.to_string(),
+            self.block_hash.clone().unwrap_or_default(),
+            format!("{}-{}", self.window_start, self.window_end),
+            self.outcome
+                .clone()
+                .unwrap_or_else(|| "in_progress".to_owned()),
+            self.attempts.to_string(),
+            self.fee_strk.to_string(),
+            self.failure_reasons.join("; "),
+            self.updated_at.to_rfc3339(),
+        ]
+    }
+}
+
+/// Attestation performance over a date range.
+#[derive(Debug, PartialEq, Serialize)]
+struct Report {
+    from: NaiveDate,
+    to: NaiveDate,
+    /// Epochs with a final outcome.
+    epochs: u64,
+    confirmed: u64,
+    attested_externally: u64,
+    missed: u64,
+    uptime_percent: Option<f64>,
+    total_fee_strk: f64,
+    /// Average fee of the epochs in which a fee was paid.
+    average_fee_strk: Option<f64>,
+    missed_epochs: Vec<u64>,
+}
+
+impl Report {
+    fn new(from: NaiveDate, to: NaiveDate, epochs: &[EpochRecord]) -> Self {
+        let count = |outcome: &str| {
+            epochs
+                .iter()
+                .filter(|epoch| epoch.outcome.as_deref() == Some(outcome))
+                .count() as u64
+        };
+        let confirmed = count("confirmed");
+        let attested_externally = count("attested_externally");
+        let missed = count("missed");
+        let completed = confirmed + attested_externally + missed;
+
+        let total_fee: u64 = epochs.iter().map(|epoch| epoch.fee).sum();
+        let paid_epochs = epochs.iter().filter(|epoch| epoch.fee > 0).count();
+
+        Self {
+            from,
+            to,
+            epochs: completed,
+            confirmed,
+            attested_externally,
+            missed,
+            uptime_percent: (completed > 0)
+                .then(|| (confirmed + attested_externally) as f64 / completed as f64 * 100.0),
+            total_fee_strk: fri_to_strk(total_fee),
+            average_fee_strk: (paid_epochs > 0)
+                .then(|| fri_to_strk(total_fee) / paid_epochs as f64),
+            missed_epochs: epochs
+                .iter()
+                .filter(|epoch| epoch.outcome.as_deref() == Some("missed"))
+                .map(|epoch| epoch.epoch_id)
+                .collect(),
+        }
+    }
+}
+
+impl Row for Report {
+    const HEADER: &'static [&'static str] = &[
+        "from",
+        "to",
+        "epochs",
+        "confirmed",
+        "attested_externally",
+        "missed",
+        "uptime_percent",
+        "total_fee_strk",
+        "average_fee_strk",
+        "missed_epochs",
+    ];
+
+    fn columns(&self) -> Vec<String> {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        vec![
+            self.from.to_string(),
+            self.to.to_string(),
+            self.epochs.to_string(),
+            self.confirmed.to_string(),
+            self.attested_externally.to_string(),
+            self.missed.to_string(),
+            optional(self.uptime_percent),
+            self.total_fee_strk.to_string(),
+            optional(self.average_fee_strk),
+            self.missed_epochs
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epoch(epoch_id: u64, outcome: Option<&str>, fee: u64) -> EpochRecord {
+        EpochRecord {
+            epoch_id,
+            block_to_attest: 0,
+            block_hash: None,
+            window_start: 11,
+            window_end: 16,
+            outcome: outcome.map(str::to_owned),
+            attempts: 1,
+            fee,
+            failure_reasons: vec![],
+            started_at: DateTime::UNIX_EPOCH,
+            updated_at: DateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn schedule() {
+        let attestation_info = AttestationInfo {
+            staker_address: Felt::from(0xdeadbeef_u64),
+            operational_address: Felt::ZERO,
+            stake: 1000,
+            epoch_len: 40,
+            epoch_id: 1,
+            current_epoch_starting_block: 30,
+            attestation_window: 20,
+        };
+
+        // Epoch 0 would start before genesis.
+        let schedule = schedule_epochs(&attestation_info, 0..3);
+
+        assert_eq!(schedule.len(), 2);
+        for (row, epoch_starting_block) in schedule.iter().zip([30, 70]) {
+            let epoch_info = AttestationInfo {
+                epoch_id: row.epoch_id,
+                current_epoch_starting_block: epoch_starting_block,
+                ..attestation_info.clone()
+            };
+            assert_eq!(row.epoch_starting_block, epoch_starting_block);
+            assert_eq!(
+                row.block_to_attest,
+                epoch_info.calculate_expected_attestation_block()
+            );
+            assert!(
+                (epoch_starting_block..epoch_starting_block + 20).contains(&row.block_to_attest)
+            );
+            assert_eq!(row.window_start, row.block_to_attest + 11);
+            assert_eq!(row.window_end, row.block_to_attest + 20);
+        }
+    }
+
+    #[test]
+    fn report() {
+        let day = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        let epochs = [
+            epoch(1, Some("confirmed"), 2_000_000_000_000_000),
+            epoch(2, Some("missed"), 1_000_000_000_000_000),
+            epoch(3, Some("attested_externally"), 0),
+            epoch(4, Some("confirmed"), 0),
+            epoch(5, None, 0),
+        ];
+
+        let report = Report::new(day, day, &epochs);
+
+        assert_eq!(
+            report,
+            Report {
+                from: day,
+                to: day,
+                epochs: 4,
+                confirmed: 2,
+                attested_externally: 1,
+                missed: 1,
+                uptime_percent: Some(75.0),
+                total_fee_strk: 0.003,
+                average_fee_strk: Some(0.0015),
+                missed_epochs: vec![2],
+            }
+        );
+    }
+}
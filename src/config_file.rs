@@ -0,0 +1,280 @@
+use std::{fmt::Write, path::Path};
+
+use anyhow::Context;
+use clap::{ArgAction, ArgMatches, parser::ValueSource};
+
+/// Options applied without a restart when the configuration is reloaded on
+/// HUP.
+pub const RELOADABLE: &[&str] = &[
+    "tip_boost",
+    "minimum_tip",
+    "remote_signer_url",
+    "remote_signer_protocol",
+    "remote_signer_timeout",
+    "remote_signer_retries",
+    "remote_signer_retry_delay",
+    "remote_signer_deadline",
+    "multisig_threshold",
+    "multisig_signer_url",
+    "multisig_timeout",
+    "treasury_low_water_mark",
+    "treasury_top_up_amount",
+    "treasury_daily_cap",
+    "log_filter",
+];
+
+pub fn read(path: &Path) -> anyhow::Result<toml::Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading configuration file {}", path.display()))?;
+    contents
+        .parse()
+        .with_context(|| format!("Parsing configuration file {}", path.display()))
+}
+
+/// Uses the values of `table` as defaults of the options of `command`, so that
+/// the command line and the environment take precedence over them.
+pub fn apply(mut command: clap::Command, table: &toml::Table) -> anyhow::Result<clap::Command> {
+    // Network profiles are not options.
+    for (key, value) in table.iter().filter(|(key, _)| *key != "networks") {
+        anyhow::ensure!(
+            key != "config" && command.get_arguments().any(|arg| arg.get_id() == key),
+            "Unknown option {key} in configuration file"
+        );
+        let values = match value {
+            toml::Value::Array(values) => values
+                .iter()
+                .map(|value| to_arg_value(key, value))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            value => vec![to_arg_value(key, value)?],
+        };
+        command = command.mut_arg(key, |arg| arg.default_values(values).required(false));
+    }
+    Ok(command)
+}
+
+fn to_arg_value(key: &str, value: &toml::Value) -> anyhow::Result<String> {
+    match value {
+        toml::Value::String(value) => Ok(value.clone()),
+        toml::Value::Integer(value) => Ok(value.to_string()),
+        toml::Value::Float(value) => Ok(value.to_string()),
+        toml::Value::Boolean(value) => Ok(value.to_string()),
+        _ => anyhow::bail!("Unsupported value of option {key} in configuration file"),
+    }
+}
+
+/// Where the effective value of an option comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Environment,
+    ConfigurationFile,
+    Default,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::CommandLine => "command line",
+            Source::Environment => "environment",
+            Source::ConfigurationFile => "configuration file",
+            Source::Default => "default",
+        }
+    }
+}
+
+/// The effective value of an option.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: toml::Value,
+    pub source: Source,
+    /// Secrets are redacted when printed.
+    pub secret: bool,
+}
+
+/// The effective values of the options of `command` that are set, in the
+/// order of `command`.
+pub fn settings(
+    command: &clap::Command,
+    matches: &ArgMatches,
+    table: &toml::Table,
+) -> Vec<Setting> {
+    command
+        .get_arguments()
+        .filter(|arg| arg.get_id() != "config")
+        .filter_map(|arg| {
+            let key = arg.get_id().as_str();
+            let values: Vec<_> = matches
+                .get_raw(key)?
+                .map(|value| toml::Value::String(value.to_string_lossy().into_owned()))
+                .collect();
+            let value = match arg.get_action() {
+                ArgAction::Append => toml::Value::Array(values),
+                _ => values.into_iter().next()?,
+            };
+            let source = match matches.value_source(key)? {
+                ValueSource::CommandLine => Source::CommandLine,
+                ValueSource::EnvVariable => Source::Environment,
+                _ if table.contains_key(key) => Source::ConfigurationFile,
+                _ => Source::Default,
+            };
+            Some(Setting {
+                key: key.to_owned(),
+                value,
+                source,
+                secret: arg.is_hide_env_values_set(),
+            })
+        })
+        .collect()
+}
+
+/// The options that differ between `old` and `new` but cannot be changed
+/// without a restart.
+pub fn changes_requiring_restart(old: &[Setting], new: &[Setting]) -> Vec<String> {
+    let value = |settings: &[Setting], key: &str| {
+        settings
+            .iter()
+            .find(|setting| setting.key == key)
+            .map(|setting| setting.value.clone())
+    };
+    let mut keys: Vec<_> = old.iter().chain(new).map(|setting| &setting.key).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| !RELOADABLE.contains(&key.as_str()))
+        .filter(|key| value(old, key) != value(new, key))
+        .cloned()
+        .collect()
+}
+
+/// The settings as TOML, with secrets and the credentials of URLs redacted and
+/// their sources as comments.
+pub fn render(settings: &[Setting]) -> String {
+    let mut output = String::new();
+    for setting in settings {
+        let value = if setting.secret {
+            toml::Value::String(REDACTED.to_owned())
+        } else if setting.key.ends_with("_url") {
+            redact_urls(&setting.value)
+        } else {
+            setting.value.clone()
+        };
+        let _ = writeln!(
+            output,
+            "{} = {value} # {}",
+            setting.key,
+            setting.source.as_str()
+        );
+    }
+    output
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/// `value` with the URLs it contains redacted like in logs.
+fn redact_urls(value: &toml::Value) -> toml::Value {
+    match value {
+        toml::Value::String(url) => toml::Value::String(match url.parse() {
+            Ok(url) => crate::secret::redact_url(&url),
+            // Not to print credentials a malformed URL may contain.
+            Err(_) => REDACTED.to_owned(),
+        }),
+        toml::Value::Array(values) => toml::Value::Array(values.iter().map(redact_urls).collect()),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, Command};
+
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("test")
+            .arg(Arg::new("tip_boost").long("tip-boost").default_value("1.0"))
+            .arg(
+                Arg::new("remote_signer_url")
+                    .long("remote-signer-url")
+                    .action(ArgAction::Append),
+            )
+            .arg(Arg::new("node_url").long("node-url").required(true))
+    }
+
+    #[test]
+    fn file_values_are_defaults() {
+        let table = "tip_boost = 1.5\nremote_signer_url = [\"http://a\", \"http://b\"]"
+            .parse()
+            .unwrap();
+        let command = apply(command(), &table).unwrap();
+
+        let matches = command
+            .clone()
+            .try_get_matches_from(["test", "--node-url", "http://node"])
+            .unwrap();
+        assert_eq!(matches.get_one::<String>("tip_boost").unwrap(), "1.5");
+        assert_eq!(
+            matches
+                .get_many::<String>("remote_signer_url")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            ["http://a", "http://b"]
+        );
+
+        let matches = command
+            .try_get_matches_from(["test", "--node-url", "http://node", "--tip-boost", "2"])
+            .unwrap();
+        assert_eq!(matches.get_one::<String>("tip_boost").unwrap(), "2");
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        for table in ["config = \"other.toml\"", "unknown = 1"] {
+            assert!(
+                apply(command(), &table.parse().unwrap()).is_err(),
+                "{table}"
+            );
+        }
+    }
+
+    #[test]
+    fn effective_settings() {
+        let table = "node_url = \"http://node/rpc/v0_10?key=s3cret\""
+            .parse()
+            .unwrap();
+        let command = apply(command(), &table)
+            .unwrap()
+            .arg(Arg::new("api_key").long("api-key").hide_env_values(true));
+        let matches = command
+            .clone()
+            .try_get_matches_from([
+                "test",
+                "--api-key",
+                "s3cret",
+                "--remote-signer-url",
+                "http://a",
+                "--remote-signer-url",
+                "https://user:password@b/v1/api-key",
+            ])
+            .unwrap();
+
+        let settings = settings(&command, &matches, &table);
+
+        assert_eq!(
+            render(&settings),
+            "tip_boost = \"1.0\" # default\n\
+             remote_signer_url = [\"http://a/\", \"https://[REDACTED]@b/v1/[REDACTED]\"] # command line\n\
+             node_url = \"http://node/rpc/v0_10?[REDACTED]\" # configuration file\n\
+             api_key = \"[REDACTED]\" # command line\n"
+        );
+
+        let mut new_settings = settings.clone();
+        new_settings[0].value = toml::Value::String("2.0".to_owned());
+        new_settings[2].value = toml::Value::String("http://other".to_owned());
+        new_settings.pop();
+        assert_eq!(
+            changes_requiring_restart(&settings, &new_settings),
+            ["api_key", "node_url"]
+        );
+    }
+}
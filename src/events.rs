@@ -9,7 +9,7 @@ use starknet_rust::macros::selector;
 use starknet_rust_tokio_tungstenite::{EventSubscriptionOptions, EventsUpdate, TungsteniteStream};
 use url::Url;
 
-const SELECTOR_STAKER_ATTESTATION_SUCCESSFUL: Felt = selector!("StakerAttestationSuccessful");
+pub const SELECTOR_STAKER_ATTESTATION_SUCCESSFUL: Felt = selector!("StakerAttestationSuccessful");
 
 #[derive(Debug)]
 pub enum AttestationEvent {
@@ -67,7 +67,9 @@ pub async fn fetch(
     }
 }
 
-fn parse_staker_attestation_successful(event: &EmittedEvent) -> anyhow::Result<AttestationEvent> {
+pub fn parse_staker_attestation_successful(
+    event: &EmittedEvent,
+) -> anyhow::Result<AttestationEvent> {
     let staker_address = *event.keys.get(1).context("Getting staker address")?;
     let event_data =
         StakerAttestationSuccessfulData::decode(&event.data).context("Parsing event data")?;
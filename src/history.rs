@@ -0,0 +1,594 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use starknet_rust::core::types::Felt;
+
+use crate::state::{EpochOutcome, State};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS epochs (
+    epoch_id INTEGER PRIMARY KEY,
+    staker_address TEXT NOT NULL,
+    block_to_attest INTEGER NOT NULL,
+    block_hash TEXT,
+    window_start INTEGER NOT NULL,
+    window_end INTEGER NOT NULL,
+    outcome TEXT,
+    started_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    epoch_id INTEGER NOT NULL REFERENCES epochs(epoch_id),
+    transaction_hash TEXT,
+    tip INTEGER,
+    fee INTEGER,
+    status TEXT NOT NULL,
+    failure_reason TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS attempts_epoch_id ON attempts(epoch_id);
+CREATE INDEX IF NOT EXISTS attempts_transaction_hash ON attempts(transaction_hash);
+";
+
+/// Status of an attempt to attest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttemptStatus {
+    /// The transaction was sent and is waiting for confirmation.
+    Submitted,
+    Confirmed,
+    Reverted,
+    /// The epoch ended without the transaction being confirmed.
+    Unconfirmed,
+    /// The transaction could not be sent.
+    Failed,
+}
+
+impl AttemptStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttemptStatus::Submitted => "submitted",
+            AttemptStatus::Confirmed => "confirmed",
+            AttemptStatus::Reverted => "reverted",
+            AttemptStatus::Unconfirmed => "unconfirmed",
+            AttemptStatus::Failed => "failed",
+        }
+    }
+}
+
+/// The outcome of an epoch left before its outcome was known.
+const UNKNOWN_OUTCOME: &str = "unknown";
+
+fn outcome_as_str(outcome: &EpochOutcome) -> &'static str {
+    match outcome {
+        EpochOutcome::Confirmed { .. } => "confirmed",
+        EpochOutcome::AttestedExternally => "attested_externally",
+        EpochOutcome::Missed => "missed",
+    }
+}
+
+/// An epoch in the history.
+#[derive(Debug, PartialEq)]
+pub struct EpochRecord {
+    pub epoch_id: u64,
+    pub block_to_attest: u64,
+    pub block_hash: Option<String>,
+    pub window_start: u64,
+    pub window_end: u64,
+    /// `None` while the epoch is in progress.
+    pub outcome: Option<String>,
+    pub attempts: u64,
+    /// Total actual fee (in FRI) of the attempts.
+    pub fee: u64,
+    pub failure_reasons: Vec<String>,
+    /// When the epoch was first recorded.
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const SELECT_EPOCHS: &str = "
+SELECT epoch_id, block_to_attest, block_hash, window_start, window_end, outcome, updated_at,
+    count(id), coalesce(sum(fee), 0), group_concat(failure_reason, char(10)), started_at
+FROM epochs LEFT JOIN attempts USING (epoch_id)
+";
+
+/// Per-epoch attestation history stored in an SQLite database.
+///
+/// Each epoch has a row in `epochs`, and each attempt to attest in that epoch
+/// has a row in `attempts`.
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Opening history database {}", path.display()))?;
+        Self::from_connection(connection)
+    }
+
+    /// Opens an existing history database for reading.
+    pub fn open_read_only(path: &Path) -> anyhow::Result<Self> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Opening history database {}", path.display()))?;
+        Ok(Self { connection })
+    }
+
+    fn from_connection(connection: Connection) -> anyhow::Result<Self> {
+        connection
+            .pragma_update(None, "journal_mode", "WAL")
+            .context("Enabling write-ahead logging")?;
+        connection
+            .execute_batch(SCHEMA)
+            .context("Creating history database schema")?;
+        add_started_at(&connection).context("Migrating history database schema")?;
+        Ok(Self { connection })
+    }
+
+    /// Refuses a database with the epochs of another staker: epochs are keyed
+    /// by their ID only, so they would overwrite each other.
+    pub fn check_staker(&self, staker_address: Felt) -> anyhow::Result<()> {
+        let other: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT staker_address FROM epochs WHERE staker_address != ?1 LIMIT 1",
+                params![format!("{staker_address:#x}")],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Reading staker address from history")?;
+        match other {
+            Some(other) => anyhow::bail!(
+                "The history database records the epochs of staker {other}, not \
+                 {staker_address:#x}, please use a separate database per staker"
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the epoch of `state`, for example after (re)initialization.
+    pub fn record_state(&mut self, state: &State) -> anyhow::Result<()> {
+        self.check_staker(state.staker_address())?;
+        upsert_epoch(&self.connection, state)
+    }
+
+    /// Records the changes between `old_state` and `new_state`.
+    pub fn record_transition(
+        &mut self,
+        old_state: &State,
+        new_state: &State,
+    ) -> anyhow::Result<()> {
+        let transaction = self.connection.transaction()?;
+        let same_epoch = old_state.epoch_id() == new_state.epoch_id();
+
+        if !same_epoch
+            || old_state.block_hash() != new_state.block_hash()
+            || old_state.outcome() != new_state.outcome()
+        {
+            upsert_epoch(&transaction, new_state)?;
+        }
+
+        let previous_failures = if same_epoch {
+            old_state.failed_attempts().len()
+        } else {
+            0
+        };
+        for reason in new_state.failed_attempts().iter().skip(previous_failures) {
+            transaction.execute(
+                "INSERT INTO attempts (epoch_id, status, failure_reason, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    new_state.epoch_id(),
+                    AttemptStatus::Failed.as_str(),
+                    reason,
+                    now()
+                ],
+            )?;
+        }
+
+        let submitted = new_state.submitted_transaction();
+        if let Some(submitted) = submitted
+            && old_state.submitted_transaction() != Some(submitted)
+        {
+            transaction.execute(
+                "INSERT INTO attempts (epoch_id, transaction_hash, tip, status, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    new_state.epoch_id(),
+                    format!("{:#x}", submitted.transaction_hash),
+                    submitted.tip,
+                    AttemptStatus::Submitted.as_str(),
+                    now()
+                ],
+            )?;
+        }
+
+        if let Some(previous) = old_state.submitted_transaction()
+            && same_epoch
+            && submitted != Some(previous)
+        {
+            let status = match new_state.outcome() {
+                Some(EpochOutcome::Confirmed {
+                    transaction_hash, ..
+                }) if *transaction_hash == previous.transaction_hash => AttemptStatus::Confirmed,
+                // Only a reverted transaction is retried within the epoch.
+                None => AttemptStatus::Reverted,
+                Some(_) => AttemptStatus::Unconfirmed,
+            };
+            transaction.execute(
+                "UPDATE attempts SET status = ?1 WHERE transaction_hash = ?2",
+                params![status.as_str(), format!("{:#x}", previous.transaction_hash)],
+            )?;
+        }
+
+        if !same_epoch && old_state.outcome().is_none() {
+            // The epoch ended while an attestation was still pending. Whether the
+            // staker attested is unknown without checking the chain.
+            if let Some(previous) = old_state.submitted_transaction() {
+                transaction.execute(
+                    "UPDATE attempts SET status = ?1 WHERE transaction_hash = ?2",
+                    params![
+                        AttemptStatus::Unconfirmed.as_str(),
+                        format!("{:#x}", previous.transaction_hash)
+                    ],
+                )?;
+            }
+            transaction.execute(
+                "UPDATE epochs SET outcome = ?1, updated_at = ?2
+                 WHERE epoch_id = ?3 AND outcome IS NULL",
+                params![UNKNOWN_OUTCOME, now(), old_state.epoch_id()],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Records the actual fee (in FRI) paid by an attestation transaction.
+    pub fn record_fee(&mut self, transaction_hash: Felt, fee: u128) -> anyhow::Result<()> {
+        let fee = i64::try_from(fee).context("Fee is too large to store")?;
+        self.connection.execute(
+            "UPDATE attempts SET fee = ?1 WHERE transaction_hash = ?2",
+            params![fee, format!("{transaction_hash:#x}")],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent `limit` epochs, newest first.
+    pub fn recent_epochs(&self, limit: usize) -> anyhow::Result<Vec<EpochRecord>> {
+        self.query_epochs(
+            "GROUP BY epoch_id ORDER BY epoch_id DESC LIMIT ?1",
+            params![limit],
+        )
+    }
+
+    /// Epochs started in `[from, to)`, oldest first.
+    pub fn epochs_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<EpochRecord>> {
+        self.query_epochs(
+            "WHERE started_at >= ?1 AND started_at < ?2 GROUP BY epoch_id ORDER BY epoch_id",
+            params![from.timestamp(), to.timestamp()],
+        )
+    }
+
+    fn query_epochs(
+        &self,
+        clauses: &str,
+        params: impl rusqlite::Params,
+    ) -> anyhow::Result<Vec<EpochRecord>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("{SELECT_EPOCHS} {clauses}"))?;
+        let epochs = statement
+            .query_map(params, |row| {
+                let failure_reasons: Option<String> = row.get(9)?;
+                Ok(EpochRecord {
+                    epoch_id: row.get(0)?,
+                    block_to_attest: row.get(1)?,
+                    block_hash: row.get(2)?,
+                    window_start: row.get(3)?,
+                    window_end: row.get(4)?,
+                    outcome: row.get(5)?,
+                    started_at: DateTime::from_timestamp(row.get(10)?, 0).unwrap_or_default(),
+                    updated_at: DateTime::from_timestamp(row.get(6)?, 0).unwrap_or_default(),
+                    attempts: row.get(7)?,
+                    fee: row.get(8)?,
+                    failure_reasons: failure_reasons
+                        .map(|reasons| reasons.lines().map(str::to_owned).collect())
+                        .unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<_, _>>()
+            .context("Reading epochs from history")?;
+        Ok(epochs)
+    }
+}
+
+fn upsert_epoch(connection: &Connection, state: &State) -> anyhow::Result<()> {
+    let window = state.attestation_window();
+    connection
+        .execute(
+            "INSERT INTO epochs (epoch_id, staker_address, block_to_attest, block_hash,
+                 window_start, window_end, outcome, started_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+             ON CONFLICT (epoch_id) DO UPDATE SET
+                 block_hash = coalesce(excluded.block_hash, block_hash),
+                 outcome = coalesce(excluded.outcome, outcome),
+                 updated_at = excluded.updated_at",
+            params![
+                state.epoch_id(),
+                format!("{:#x}", state.staker_address()),
+                state.block_to_attest(),
+                state
+                    .block_hash()
+                    .map(|block_hash| format!("{block_hash:#x}")),
+                window.start,
+                window.end,
+                state.outcome().map(outcome_as_str),
+                now()
+            ],
+        )
+        .context("Recording epoch")?;
+    Ok(())
+}
+
+/// Adds `epochs.started_at` to databases created before it existed, using the
+/// last update as the best known approximation.
+fn add_started_at(connection: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT count(*) > 0 FROM pragma_table_info('epochs') WHERE name = 'started_at'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute_batch(
+            "ALTER TABLE epochs ADD COLUMN started_at INTEGER NOT NULL DEFAULT 0;
+             UPDATE epochs SET started_at = updated_at;",
+        )?;
+    }
+    Ok(())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::macros::felt;
+
+    use crate::{attestation_info::AttestationInfo, state::AttestationParams};
+
+    use super::*;
+
+    const TRANSACTION_HASH: Felt = felt!("0x1234");
+
+    fn history() -> History {
+        History::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn attestation_info() -> AttestationInfo {
+        AttestationInfo {
+            staker_address: felt!("0xdeadbeef"),
+            operational_address: felt!("0xfeedbeef"),
+            stake: 1000000000000000000,
+            epoch_len: 40,
+            epoch_id: 1,
+            current_epoch_starting_block: 20,
+            attestation_window: 16,
+        }
+    }
+
+    fn attempts(history: &History) -> Vec<(Option<String>, String, Option<String>)> {
+        let mut statement = history
+            .connection
+            .prepare("SELECT transaction_hash, status, failure_reason FROM attempts ORDER BY id")
+            .unwrap();
+        statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn records_attempts_and_outcome() {
+        let mut history = history();
+        let state = State::from_attestation_info(attestation_info());
+        history.record_state(&state).unwrap();
+
+        let attestation_params = AttestationParams::new(24, felt!("0xabcdef"), 16);
+        let failed = State::Attesting {
+            attestation_info: attestation_info(),
+            attestation_params: attestation_params.clone(),
+            failed_attempts: vec!["Signer unavailable".to_owned()],
+            sponsored_execution: None,
+        };
+        history.record_transition(&state, &failed).unwrap();
+        let submitted = State::AttestationSubmitted {
+            attestation_info: attestation_info(),
+            attestation_params,
+            transaction_hash: TRANSACTION_HASH,
+            tip: Some(5),
+        };
+        history.record_transition(&failed, &submitted).unwrap();
+        let confirmed = State::WaitingForNextEpoch {
+            attestation_info: attestation_info(),
+            outcome: EpochOutcome::Confirmed {
+                transaction_hash: TRANSACTION_HASH,
+                tip: Some(5),
+            },
+        };
+        history.record_transition(&submitted, &confirmed).unwrap();
+        history
+            .record_fee(TRANSACTION_HASH, 1_000_000_000_000_000)
+            .unwrap();
+
+        assert_eq!(
+            attempts(&history),
+            vec![
+                (
+                    None,
+                    "failed".to_owned(),
+                    Some("Signer unavailable".to_owned())
+                ),
+                (Some("0x1234".to_owned()), "confirmed".to_owned(), None),
+            ]
+        );
+        let (outcome, block_hash, fee): (String, Option<String>, i64) = history
+            .connection
+            .query_row(
+                "SELECT outcome, block_hash, fee FROM epochs JOIN attempts USING (epoch_id)
+                 WHERE transaction_hash IS NOT NULL",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(outcome, "confirmed");
+        assert_eq!(block_hash.as_deref(), Some("0xabcdef"));
+        assert_eq!(fee, 1_000_000_000_000_000);
+
+        let epochs = history.recent_epochs(10).unwrap();
+        assert_eq!(epochs.len(), 1);
+        assert_eq!(epochs[0].outcome.as_deref(), Some("confirmed"));
+        assert_eq!(epochs[0].attempts, 2);
+        assert_eq!(epochs[0].fee, 1_000_000_000_000_000);
+        assert_eq!(epochs[0].failure_reasons, vec!["Signer unavailable"]);
+        assert_eq!(
+            history
+                .epochs_between(epochs[0].started_at, epochs[0].started_at)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn epochs_are_selected_by_start_time() {
+        let mut history = history();
+        let state = State::from_attestation_info(attestation_info());
+        history.record_state(&state).unwrap();
+        history
+            .connection
+            .execute("UPDATE epochs SET started_at = 1000, updated_at = 2000", [])
+            .unwrap();
+        // Finishing the epoch later does not move it out of the range it started in.
+        let missed = State::WaitingForNextEpoch {
+            attestation_info: attestation_info(),
+            outcome: EpochOutcome::Missed,
+        };
+        history.record_transition(&state, &missed).unwrap();
+
+        let epoch_ids = |from: i64, to: i64| -> Vec<u64> {
+            history
+                .epochs_between(
+                    DateTime::from_timestamp(from, 0).unwrap(),
+                    DateTime::from_timestamp(to, 0).unwrap(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|epoch| epoch.epoch_id)
+                .collect()
+        };
+        assert_eq!(epoch_ids(1000, 1001), vec![1]);
+        assert_eq!(epoch_ids(1001, 3000), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn other_stakers_are_refused() {
+        let mut history = history();
+        history
+            .record_state(&State::from_attestation_info(attestation_info()))
+            .unwrap();
+        history.check_staker(felt!("0xdeadbeef")).unwrap();
+
+        let other = AttestationInfo {
+            staker_address: felt!("0xbeef"),
+            ..attestation_info()
+        };
+        assert!(history.check_staker(other.staker_address).is_err());
+        assert!(
+            history
+                .record_state(&State::from_attestation_info(other))
+                .is_err()
+        );
+        assert_eq!(
+            history.recent_epochs(10).unwrap()[0].block_to_attest,
+            State::from_attestation_info(attestation_info()).block_to_attest()
+        );
+    }
+
+    #[test]
+    fn started_at_is_added_to_existing_databases() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE epochs (
+                    epoch_id INTEGER PRIMARY KEY,
+                    staker_address TEXT NOT NULL,
+                    block_to_attest INTEGER NOT NULL,
+                    block_hash TEXT,
+                    window_start INTEGER NOT NULL,
+                    window_end INTEGER NOT NULL,
+                    outcome TEXT,
+                    updated_at INTEGER NOT NULL
+                );
+                INSERT INTO epochs VALUES (1, '0xdeadbeef', 24, NULL, 25, 40, 'missed', 1500);",
+            )
+            .unwrap();
+
+        let history = History::from_connection(connection).unwrap();
+
+        let epochs = history.recent_epochs(10).unwrap();
+        assert_eq!(
+            epochs[0].started_at,
+            DateTime::from_timestamp(1500, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn pending_attempt_is_unconfirmed_when_epoch_changes() {
+        let mut history = history();
+        let attestation_params = AttestationParams::new(24, felt!("0xabcdef"), 16);
+        let submitted = State::AttestationSubmitted {
+            attestation_info: attestation_info(),
+            attestation_params,
+            transaction_hash: TRANSACTION_HASH,
+            tip: None,
+        };
+        history.record_state(&submitted).unwrap();
+        history
+            .record_transition(
+                &State::from_attestation_info(attestation_info()),
+                &submitted,
+            )
+            .unwrap();
+
+        let next_epoch = State::from_attestation_info(AttestationInfo {
+            epoch_id: 2,
+            current_epoch_starting_block: 60,
+            ..attestation_info()
+        });
+        history.record_transition(&submitted, &next_epoch).unwrap();
+
+        assert_eq!(
+            attempts(&history),
+            vec![(Some("0x1234".to_owned()), "unconfirmed".to_owned(), None)]
+        );
+        let epochs = history.recent_epochs(10).unwrap();
+        assert_eq!(
+            epochs
+                .iter()
+                .map(|epoch| (epoch.epoch_id, epoch.outcome.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(2, None), (1, Some("unknown"))]
+        );
+    }
+}
@@ -5,8 +5,8 @@ use starknet_rust::{
         types::{
             BlockId, BlockTag, BroadcastedInvokeTransactionV3, ContractExecutionError,
             DataAvailabilityMode, Felt, FunctionCall, InnerContractExecutionError,
-            MaybePreConfirmedBlockWithTxHashes, ResourceBounds, ResourceBoundsMapping,
-            TransactionStatus,
+            MaybePreConfirmedBlockWithTxHashes, PriceUnit, ResourceBounds, ResourceBoundsMapping,
+            TransactionReceipt, TransactionStatus,
         },
         utils::get_selector_from_name,
     },
@@ -15,14 +15,43 @@ use starknet_rust::{
 
 use crate::{
     attestation_info::AttestationInfo,
-    signer::{AttestationSigner, SignError},
+    paymaster::Paymaster,
+    signer::{
+        AttestationContext, AttestationSigner, SignError, SignatureFormat, SigningRequest,
+        TypedDataSigningRequest,
+    },
     tip::TipCalculationParams,
 };
 
+/// An attestation transaction sent to the network.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttestationTransaction {
+    pub transaction_hash: Felt,
+    /// The tip per L2 gas unit, or `None` if the transaction was sponsored by
+    /// a paymaster.
+    pub tip: Option<u64>,
+}
+
+/// An outside execution signed for the paymaster, which may be relayed until
+/// `execute_before`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SponsoredExecution {
+    pub message_hash: Felt,
+    /// Seconds since the Unix epoch.
+    pub execute_before: u64,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ClientError {
     #[error("Attestation failed: {0}")]
     AttestationFailed(String),
+    /// The paymaster may still relay the signed outside execution, so no other
+    /// attestation should be sent before it expires.
+    #[error("Sponsored attestation may have been sent: {error:#}")]
+    MaybeSent {
+        execution: SponsoredExecution,
+        error: anyhow::Error,
+    },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -61,13 +90,14 @@ impl<E: std::error::Error + Send + Sync + 'static> From<AccountError<E>> for Cli
 }
 
 pub trait Client {
-    async fn attest(
+    async fn attest<S: AttestationSigner + ?Sized>(
         &self,
-        operational_address: Felt,
-        signer: &AttestationSigner,
+        signer: &S,
         tip_calculation_params: &TipCalculationParams,
+        attestation_info: &AttestationInfo,
+        block_number: u64,
         block_hash: Felt,
-    ) -> Result<Felt, ClientError>;
+    ) -> Result<AttestationTransaction, ClientError>;
     async fn attestation_done_in_current_epoch(
         &self,
         staker_address: Felt,
@@ -82,6 +112,23 @@ pub trait Client {
     ) -> Result<AttestationInfo, ClientError>;
     async fn get_block_hash(&self, block_number: u64) -> Result<Felt, ClientError>;
     async fn get_strk_balance(&self, account_address: Felt) -> Result<u128, ClientError>;
+    /// The actual fee (in FRI) charged for an invoke transaction.
+    async fn get_transaction_fee(&self, transaction_hash: Felt) -> Result<u128, ClientError>;
+}
+
+/// Why an attestation sponsored by the paymaster failed.
+enum SponsoredAttestationError {
+    /// The paymaster has not accepted the outside execution, so a self-paid
+    /// attestation can be sent instead.
+    NotSent(anyhow::Error),
+    /// The paymaster may still relay the signed outside execution.
+    MaybeSent(SponsoredExecution, anyhow::Error),
+}
+
+impl SponsoredAttestationError {
+    fn not_sent(error: impl Into<anyhow::Error>, context: &'static str) -> Self {
+        Self::NotSent(error.into().context(context))
+    }
 }
 
 pub struct StarknetRpcClient {
@@ -89,18 +136,60 @@ pub struct StarknetRpcClient {
     staking_contract_address: Felt,
     attestation_contract_address: Felt,
     strk_contract_address: Felt,
+    signature_format: SignatureFormat,
+    paymaster: Option<Paymaster>,
 }
 
 impl Client for StarknetRpcClient {
-    async fn attest(
+    async fn attest<S: AttestationSigner + ?Sized>(
         &self,
-        operational_address: Felt,
-        signer: &AttestationSigner,
+        signer: &S,
         tip_calculation_params: &TipCalculationParams,
+        attestation_info: &AttestationInfo,
+        block_number: u64,
         block_hash: Felt,
-    ) -> Result<Felt, ClientError> {
+    ) -> Result<AttestationTransaction, ClientError> {
+        let context = AttestationContext {
+            staker_address: attestation_info.staker_address,
+            epoch_id: attestation_info.epoch_id,
+            block_number,
+            block_hash,
+            attestation_contract_address: self.attestation_contract_address,
+        };
+
         let chain_id = self.client.chain_id().await.context("Getting chain ID")?;
 
+        if let Some(paymaster) = &self.paymaster {
+            match self
+                .attest_sponsored(
+                    paymaster,
+                    signer,
+                    chain_id,
+                    attestation_info.operational_address,
+                    context.clone(),
+                )
+                .await
+            {
+                Ok(transaction_hash) => {
+                    metrics::counter!("validator_attestation_paymaster_sponsored_count")
+                        .increment(1);
+                    return Ok(AttestationTransaction {
+                        transaction_hash,
+                        tip: None,
+                    });
+                }
+                Err(SponsoredAttestationError::NotSent(error)) => {
+                    tracing::warn!(error=?error, "Paymaster did not sponsor attestation, falling back to self-paid transaction");
+                    metrics::counter!("validator_attestation_paymaster_fallback_count")
+                        .increment(1);
+                }
+                // Sending a self-paid attestation as well could attest twice.
+                Err(SponsoredAttestationError::MaybeSent(execution, error)) => {
+                    return Err(ClientError::MaybeSent { execution, error });
+                }
+            }
+        }
+
         // Calculate tip as the median value from the latest block and apply params.
         let latest_block = self
             .client
@@ -109,7 +198,14 @@ impl Client for StarknetRpcClient {
         let current_median_tip = latest_block.median_tip();
         let tip = tip_calculation_params.calculate_tip(current_median_tip);
 
-        let account = ClearSigningAccount::new(&self.client, signer, operational_address, chain_id);
+        let account = ClearSigningAccount::new(
+            &self.client,
+            signer,
+            attestation_info.operational_address,
+            chain_id,
+            self.signature_format,
+            context,
+        );
 
         let result = account
             .execute_v3(vec![starknet_rust::core::types::Call {
@@ -124,7 +220,10 @@ impl Client for StarknetRpcClient {
             .await
             .context("Sending transaction")?;
 
-        Ok(result.transaction_hash)
+        Ok(AttestationTransaction {
+            transaction_hash: result.transaction_hash,
+            tip: Some(tip),
+        })
     }
 
     async fn attestation_status(
@@ -233,6 +332,28 @@ impl Client for StarknetRpcClient {
         let balance: u128 = result[0].try_into().context("Converting STRK balance")?;
         Ok(balance)
     }
+
+    async fn get_transaction_fee(&self, transaction_hash: Felt) -> Result<u128, ClientError> {
+        let receipt = self
+            .client
+            .get_transaction_receipt(transaction_hash)
+            .await?;
+        let TransactionReceipt::Invoke(receipt) = receipt.receipt else {
+            return Err(anyhow::anyhow!("Not an invoke transaction receipt").into());
+        };
+        if receipt.actual_fee.unit != PriceUnit::Fri {
+            return Err(
+                anyhow::anyhow!("Unexpected fee unit {:?}", receipt.actual_fee.unit).into(),
+            );
+        }
+
+        let fee: u128 = receipt
+            .actual_fee
+            .amount
+            .try_into()
+            .context("Converting actual fee")?;
+        Ok(fee)
+    }
 }
 
 impl StarknetRpcClient {
@@ -241,15 +362,78 @@ impl StarknetRpcClient {
         staking_contract_address: Felt,
         attestation_contract_address: Felt,
         strk_contract_address: Felt,
+        signature_format: SignatureFormat,
+        paymaster: Option<Paymaster>,
     ) -> Self {
         StarknetRpcClient {
             client,
             staking_contract_address,
             attestation_contract_address,
             strk_contract_address,
+            signature_format,
+            paymaster,
         }
     }
 
+    /// Sends the attestation as an outside execution sponsored by the paymaster.
+    async fn attest_sponsored<S: AttestationSigner + ?Sized>(
+        &self,
+        paymaster: &Paymaster,
+        signer: &S,
+        chain_id: Felt,
+        operational_address: Felt,
+        context: AttestationContext,
+    ) -> Result<Felt, SponsoredAttestationError> {
+        let transaction = paymaster
+            .build_transaction(
+                chain_id,
+                operational_address,
+                self.attestation_contract_address,
+                "attest",
+                vec![context.block_hash],
+            )
+            .await
+            .map_err(|error| {
+                SponsoredAttestationError::not_sent(error, "Building sponsored transaction")
+            })?;
+
+        let request = TypedDataSigningRequest {
+            message_hash: transaction.message_hash,
+            typed_data: transaction.typed_data.clone(),
+            account_address: operational_address,
+            context,
+        };
+        let signature = signer.sign_typed_data(&request).await.map_err(|error| {
+            SponsoredAttestationError::not_sent(error, "Signing outside execution")
+        })?;
+        let signature = self
+            .signature_format
+            .apply(signer, signature)
+            .await
+            .map_err(|error| {
+                SponsoredAttestationError::not_sent(error, "Signing outside execution")
+            })?;
+
+        paymaster
+            .execute_transaction(operational_address, &transaction, signature)
+            .await
+            .map_err(|error| {
+                let refusal = error.is_refusal();
+                let error = anyhow::Error::from(error).context("Executing sponsored transaction");
+                if refusal {
+                    SponsoredAttestationError::NotSent(error)
+                } else {
+                    SponsoredAttestationError::MaybeSent(
+                        SponsoredExecution {
+                            message_hash: transaction.message_hash,
+                            execute_before: transaction.execute_before,
+                        },
+                        error,
+                    )
+                }
+            })
+    }
+
     async fn get_attestation_window(&self) -> anyhow::Result<u16> {
         let result = self
             .client
@@ -269,8 +453,13 @@ impl StarknetRpcClient {
         Ok(attestation_window)
     }
 
-    pub async fn chain_id_as_string(&self) -> Result<String, ClientError> {
+    pub async fn chain_id(&self) -> Result<Felt, ClientError> {
         let chain_id = self.client.chain_id().await.context("Getting chain ID")?;
+        Ok(chain_id)
+    }
+
+    pub async fn chain_id_as_string(&self) -> Result<String, ClientError> {
+        let chain_id = self.chain_id().await?;
         let chain_id = starknet_rust::core::utils::parse_cairo_short_string(&chain_id)
             .context("Parsing chain ID as Cairo short string")?;
         Ok(chain_id)
@@ -278,30 +467,42 @@ impl StarknetRpcClient {
 }
 
 #[derive(Debug, Clone)]
-struct ClearSigningAccount<'a, P: Provider + Send> {
+struct ClearSigningAccount<'a, P: Provider + Send, S: AttestationSigner + ?Sized> {
     provider: P,
-    signer: &'a AttestationSigner,
+    signer: &'a S,
     address: Felt,
     chain_id: Felt,
     block_id: BlockId,
+    signature_format: SignatureFormat,
+    context: AttestationContext,
 }
 
-impl<'a, P: Provider + Send + Sync> ClearSigningAccount<'a, P> {
-    pub fn new(provider: P, signer: &'a AttestationSigner, address: Felt, chain_id: Felt) -> Self {
+impl<'a, P: Provider + Send + Sync, S: AttestationSigner + ?Sized> ClearSigningAccount<'a, P, S> {
+    pub fn new(
+        provider: P,
+        signer: &'a S,
+        address: Felt,
+        chain_id: Felt,
+        signature_format: SignatureFormat,
+        context: AttestationContext,
+    ) -> Self {
         Self {
             provider,
             signer,
             address,
             chain_id,
             block_id: BlockId::Tag(BlockTag::Latest),
+            signature_format,
+            context,
         }
     }
 }
 
 #[async_trait::async_trait]
-impl<P> Account for ClearSigningAccount<'_, P>
+impl<P, S> Account for ClearSigningAccount<'_, P, S>
 where
     P: Provider + Sync + Send,
+    S: AttestationSigner + ?Sized,
 {
     type SignError = SignError;
 
@@ -318,15 +519,21 @@ where
         execution: &starknet_rust::accounts::RawExecutionV3,
         query_only: bool,
     ) -> Result<Vec<Felt>, Self::SignError> {
-        let tx_hash = execution.transaction_hash(self.chain_id, self.address, query_only, self);
-        let transaction = self.get_invoke_request(execution, query_only);
-
-        let signature = self
-            .signer
-            .sign(&tx_hash, transaction, self.chain_id)
-            .await?;
-
-        Ok(signature)
+        let request = SigningRequest {
+            transaction_hash: execution.transaction_hash(
+                self.chain_id,
+                self.address,
+                query_only,
+                self,
+            ),
+            transaction: self.get_invoke_request(execution, query_only),
+            chain_id: self.chain_id,
+            context: self.context.clone(),
+        };
+
+        self.signer
+            .sign_with_format(&request, self.signature_format)
+            .await
     }
 
     async fn sign_declaration_v3(
@@ -341,13 +548,14 @@ where
         &self,
         context: starknet_rust::signers::SignerInteractivityContext<'_>,
     ) -> bool {
-        self.signer.is_signer_interactive(context)
+        self.signer.is_interactive(context)
     }
 }
 
-impl<P> starknet_rust::accounts::ExecutionEncoder for ClearSigningAccount<'_, P>
+impl<P, S> starknet_rust::accounts::ExecutionEncoder for ClearSigningAccount<'_, P, S>
 where
     P: Provider + Send,
+    S: AttestationSigner + ?Sized,
 {
     fn encode_calls(&self, calls: &[starknet_rust::core::types::Call]) -> Vec<Felt> {
         let mut execute_calldata: Vec<Felt> = vec![calls.len().into()];
@@ -364,9 +572,10 @@ where
     }
 }
 
-impl<P> starknet_rust::accounts::ConnectedAccount for ClearSigningAccount<'_, P>
+impl<P, S> starknet_rust::accounts::ConnectedAccount for ClearSigningAccount<'_, P, S>
 where
     P: Provider + Sync + Send,
+    S: AttestationSigner + ?Sized,
 {
     type Provider = P;
 
@@ -379,7 +588,7 @@ where
     }
 }
 
-impl<P: Provider + Send + Sync> ClearSigningAccount<'_, P> {
+impl<P: Provider + Send + Sync, S: AttestationSigner + ?Sized> ClearSigningAccount<'_, P, S> {
     fn get_invoke_request(
         &self,
         execution: &starknet_rust::accounts::RawExecutionV3,
@@ -415,3 +624,95 @@ impl<P: Provider + Send + Sync> ClearSigningAccount<'_, P> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::core::{chain_id, crypto::Signature, types::Call};
+    use starknet_rust::macros::felt;
+    use starknet_rust::signers::{LocalWallet, SigningKey};
+
+    use super::*;
+
+    const TRANSACTION_HASH: Felt =
+        felt!("0x382a7406fe3931ba1faf00d1eaa36b7c8770b8d185b091b730ecdb4dba5f3ce");
+
+    /// Signs the transaction of the test vector in `examples/signer/hash.rs`.
+    async fn sign(signing_key: &SigningKey, signature_format: SignatureFormat) -> Vec<Felt> {
+        let provider = JsonRpcClient::new(HttpTransport::new(
+            url::Url::parse("http://localhost:1").unwrap(),
+        ));
+        let signer = LocalWallet::from_signing_key(signing_key.clone());
+        let block_hash = felt!("0x7979a0a0a175d7e738e8e9ba6fa6d48f680d67758f719390eee58e790819836");
+        let account = ClearSigningAccount::new(
+            provider,
+            &signer,
+            felt!("0x2e216b191ac966ba1d35cb6cfddfaf9c12aec4dfe869d9fa6233611bb334ee9"),
+            chain_id::SEPOLIA,
+            signature_format,
+            AttestationContext {
+                staker_address: felt!("0xdeadbeef"),
+                epoch_id: 1,
+                block_number: 2,
+                block_hash,
+                attestation_contract_address: felt!(
+                    "0x3f32e152b9637c31bfcf73e434f78591067a01ba070505ff6ee195642c9acfb"
+                ),
+            },
+        );
+
+        let execution = account
+            .execute_v3(vec![Call {
+                to: felt!("0x3f32e152b9637c31bfcf73e434f78591067a01ba070505ff6ee195642c9acfb"),
+                selector: get_selector_from_name("attest").unwrap(),
+                calldata: vec![block_hash],
+            }])
+            .nonce(felt!("0x106"))
+            .l1_gas(0)
+            .l1_gas_price(0x51066a69ad72c)
+            .l1_data_gas(0x600)
+            .l1_data_gas_price(0x1254)
+            .l2_gas(0xf00000)
+            .l2_gas_price(0x308c5bff6)
+            .tip(0)
+            .prepared()
+            .unwrap();
+        assert_eq!(execution.transaction_hash(false), TRANSACTION_HASH);
+
+        execution
+            .get_invoke_request(false, false)
+            .await
+            .unwrap()
+            .signature
+    }
+
+    fn verify(signing_key: &SigningKey, r: Felt, s: Felt) -> bool {
+        starknet_rust::core::crypto::ecdsa_verify(
+            &signing_key.verifying_key().scalar(),
+            &TRANSACTION_HASH,
+            &Signature { r, s },
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn standard_signature_format() {
+        let signing_key = SigningKey::from_secret_scalar(felt!("0x1234"));
+
+        let signature = sign(&signing_key, SignatureFormat::Standard).await;
+
+        assert_eq!(signature.len(), 2);
+        assert!(verify(&signing_key, signature[0], signature[1]));
+    }
+
+    #[tokio::test]
+    async fn argent_signature_format() {
+        let signing_key = SigningKey::from_secret_scalar(felt!("0x1234"));
+
+        let signature = sign(&signing_key, SignatureFormat::Argent).await;
+
+        assert_eq!(signature.len(), 5);
+        assert_eq!(signature[..2], [Felt::ONE, Felt::ZERO]);
+        assert_eq!(signature[2], signing_key.verifying_key().scalar());
+        assert!(verify(&signing_key, signature[3], signature[4]));
+    }
+}
@@ -1,28 +1,58 @@
+use std::{path::PathBuf, sync::Arc};
+
 use anyhow::Context;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
 use jsonrpc::Client;
+use secret::Secret;
+use signer::AttestationSigner;
 use starknet_rust::{
     core::types::Felt,
-    macros::felt,
     providers::{JsonRpcClient, Provider, jsonrpc::HttpTransport},
     signers::{LocalWallet, SigningKey},
 };
 use tokio::select;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
 use url::Url;
 
+mod admin;
 mod attestation_info;
+mod backfill;
+mod commands;
+mod config_file;
 mod events;
 mod headers;
+mod history;
 mod jsonrpc;
 mod metrics_exporter;
+mod multisig;
+mod network;
+mod output;
+mod paymaster;
+mod rpc_version;
+mod runway;
+mod secret;
 mod signer;
 mod state;
+mod status;
 mod tip;
+mod treasury;
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Config {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+
+    #[arg(
+        long,
+        long_help = "Read the options from a TOML file, with the option names as \
+                     keys, for example 'tip_boost = 1.5'. Options given on the command line or in \
+                     the environment take precedence. The file is re-read on HUP.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_CONFIG"
+    )]
+    config: Option<PathBuf>,
+
     #[arg(
         long,
         long_help = "The address of the staking contract.",
@@ -37,22 +67,43 @@ struct Config {
         env = "VALIDATOR_ATTESTATION_ATTESTATION_CONTRACT_ADDRESS"
     )]
     attestation_contract_address: Option<Felt>,
+    #[arg(
+        long,
+        long_help = "The address of the STRK token contract.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_STRK_CONTRACT_ADDRESS"
+    )]
+    strk_contract_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The network the node must be on: 'mainnet', 'sepolia', the name of a \
+                     network profile in the configuration file, or a chain ID such as \
+                     'SN_MAIN'. The chain of the node is checked at startup and whenever the \
+                     connection to the node is re-established, and the tool refuses to run on \
+                     any other chain.",
+        value_name = "NETWORK",
+        env = "VALIDATOR_ATTESTATION_NETWORK"
+    )]
+    network: Option<String>,
 
     #[arg(
         long,
         long_help = "The address of the staker's operational account.",
         value_name = "ADDRESS",
-        env = "VALIDATOR_ATTESTATION_STAKER_OPERATIONAL_ADDRESS"
+        env = "VALIDATOR_ATTESTATION_STAKER_OPERATIONAL_ADDRESS",
+        required = true
     )]
-    staker_operational_address: Felt,
+    staker_operational_address: Option<Felt>,
 
     #[arg(
         long,
         long_help = "The URL of the Starknet node's JSON-RPC endpoint.",
         value_name = "URL",
-        env = "VALIDATOR_ATTESTATION_STARKNET_NODE_URL"
+        env = "VALIDATOR_ATTESTATION_STARKNET_NODE_URL",
+        required = true
     )]
-    pub node_url: Url,
+    pub node_url: Option<Url>,
 
     #[arg(
         long,
@@ -64,20 +115,260 @@ struct Config {
 
     #[arg(
         long,
-        long_help = "Use a local signer. The private key should be set in the environment \
-                     variable VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY.",
+        long_help = "Use a local signer. The private key should be set using \
+                     --operational-private-key or --operational-private-key-file.",
         group = "signer"
     )]
     pub local_signer: bool,
 
     #[arg(
         long,
-        long_help = "Use a remote signer at URL.",
+        long_help = "The private key of the operational account, used by the local signer. \
+                     Prefer setting this in the environment or reading it from a file instead \
+                     of passing it on the command line.",
+        value_name = "KEY",
+        env = "VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY",
+        hide_env_values = true
+    )]
+    pub operational_private_key: Option<Secret>,
+
+    #[arg(
+        long,
+        long_help = "Read the private key of the operational account from a file, for example \
+                     a mounted secret.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY_FILE"
+    )]
+    pub operational_private_key_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        long_help = "Use a remote signer at URL. Use unix:///path/to/socket to connect to a \
+                     signer listening on a Unix domain socket. Multiple comma-separated URLs \
+                     fronting the same key can be given, they are tried in order.",
         value_name = "URL",
+        value_delimiter = ',',
         env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_URL",
         group = "signer"
     )]
-    pub remote_signer_url: Option<Url>,
+    pub remote_signer_url: Vec<Url>,
+
+    #[arg(
+        long,
+        long_help = "The version of the request body sent to the remote signer. Version 2 \
+                     also includes the transaction hash and the details of the attestation.",
+        default_value = "v1",
+        value_name = "VERSION",
+        env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_PROTOCOL"
+    )]
+    pub remote_signer_protocol: signer::RemoteSignerProtocol,
+
+    #[arg(
+        long,
+        long_help = "Timeout of a single request to the remote signer.",
+        default_value = "60",
+        value_name = "SECONDS",
+        env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_TIMEOUT"
+    )]
+    pub remote_signer_timeout: u64,
+
+    #[arg(
+        long,
+        long_help = "Number of times to retry signing after all remote signer URLs have failed.",
+        default_value = "2",
+        value_name = "COUNT",
+        env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_RETRIES"
+    )]
+    pub remote_signer_retries: u32,
+
+    #[arg(
+        long,
+        long_help = "Delay before the first remote signer retry. The delay is doubled for each \
+                     subsequent retry.",
+        default_value = "500",
+        value_name = "MILLISECONDS",
+        env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_RETRY_DELAY"
+    )]
+    pub remote_signer_retry_delay: u64,
+
+    #[arg(
+        long,
+        long_help = "Overall time allowed for signing, over all remote signer URLs and \
+                     retries. New blocks are not handled while signing, so keep this below the \
+                     block time.",
+        default_value = "5",
+        value_name = "SECONDS",
+        env = "VALIDATOR_ATTESTATION_REMOTE_SIGNER_DEADLINE"
+    )]
+    pub remote_signer_deadline: u64,
+
+    #[arg(
+        long,
+        long_help = "Sign with a multisig operational account, requiring signatures from \
+                     THRESHOLD signers. The signers are the local signer or remote signer, if \
+                     configured, followed by the signers given with --multisig-signer-url.",
+        value_name = "THRESHOLD",
+        env = "VALIDATOR_ATTESTATION_MULTISIG_THRESHOLD"
+    )]
+    pub multisig_threshold: Option<usize>,
+
+    #[arg(
+        long,
+        long_help = "Comma-separated URLs of additional remote signers of a multisig operational \
+                     account. Each URL is a separate signer.",
+        value_name = "URL",
+        value_delimiter = ',',
+        env = "VALIDATOR_ATTESTATION_MULTISIG_SIGNER_URL",
+        requires = "multisig_threshold"
+    )]
+    pub multisig_signer_url: Vec<Url>,
+
+    #[arg(
+        long,
+        long_help = "How the signatures of the multisig signers are combined into the \
+                     transaction signature.",
+        default_value = "concatenated",
+        value_name = "LAYOUT",
+        env = "VALIDATOR_ATTESTATION_MULTISIG_LAYOUT"
+    )]
+    pub multisig_layout: multisig::MultisigLayout,
+
+    #[arg(
+        long,
+        long_help = "Time to wait for the multisig threshold to be met before giving up on a \
+                     signing request.",
+        default_value = "30",
+        value_name = "SECONDS",
+        env = "VALIDATOR_ATTESTATION_MULTISIG_TIMEOUT"
+    )]
+    pub multisig_timeout: u64,
+
+    #[arg(
+        long,
+        long_help = "The signature layout expected by the operational account. Use 'argent' \
+                     for Argent accounts, 'standard' for OpenZeppelin, Ready and Braavos \
+                     accounts. Multisig accounts use --multisig-layout instead.",
+        default_value = "standard",
+        value_name = "FORMAT",
+        env = "VALIDATOR_ATTESTATION_SIGNATURE_FORMAT",
+        conflicts_with = "multisig_threshold"
+    )]
+    pub signature_format: signer::SignatureFormat,
+
+    #[arg(
+        long,
+        long_help = "The URL of a SNIP-29 paymaster sponsoring attestation transactions. \
+                     Attestations fall back to transactions paid by the operational account if \
+                     the paymaster refuses to sponsor them.",
+        value_name = "URL",
+        env = "VALIDATOR_ATTESTATION_PAYMASTER_URL"
+    )]
+    pub paymaster_url: Option<Url>,
+
+    #[arg(
+        long,
+        long_help = "The API key sent to the paymaster.",
+        value_name = "KEY",
+        env = "VALIDATOR_ATTESTATION_PAYMASTER_API_KEY",
+        hide_env_values = true,
+        requires = "paymaster_url"
+    )]
+    pub paymaster_api_key: Option<Secret>,
+
+    #[arg(
+        long,
+        long_help = "Read the API key sent to the paymaster from a file.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_PAYMASTER_API_KEY_FILE",
+        requires = "paymaster_url"
+    )]
+    pub paymaster_api_key_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        long_help = "The address of a treasury account topping up the operational account with \
+                     STRK when its balance is low at the start of an epoch.",
+        value_name = "ADDRESS",
+        env = "VALIDATOR_ATTESTATION_TREASURY_ADDRESS",
+        requires_all = [
+            "treasury_low_water_mark",
+            "treasury_top_up_amount",
+            "treasury_daily_cap",
+            "treasury_transfers_file"
+        ]
+    )]
+    pub treasury_address: Option<Felt>,
+
+    #[arg(
+        long,
+        long_help = "The private key of the treasury account. Prefer setting this in the \
+                     environment or reading it from a file instead of passing it on the command \
+                     line.",
+        value_name = "KEY",
+        env = "VALIDATOR_ATTESTATION_TREASURY_PRIVATE_KEY",
+        hide_env_values = true,
+        requires = "treasury_address"
+    )]
+    pub treasury_private_key: Option<Secret>,
+
+    #[arg(
+        long,
+        long_help = "Read the private key of the treasury account from a file.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_TREASURY_PRIVATE_KEY_FILE",
+        requires = "treasury_address"
+    )]
+    pub treasury_private_key_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        long_help = "Top up the operational account if its balance is below this amount.",
+        value_name = "STRK",
+        value_parser = treasury::parse_strk,
+        env = "VALIDATOR_ATTESTATION_TREASURY_LOW_WATER_MARK",
+        requires = "treasury_address"
+    )]
+    pub treasury_low_water_mark: Option<u128>,
+
+    #[arg(
+        long,
+        long_help = "The amount transferred to the operational account by a top-up.",
+        value_name = "STRK",
+        value_parser = treasury::parse_strk,
+        env = "VALIDATOR_ATTESTATION_TREASURY_TOP_UP_AMOUNT",
+        requires = "treasury_address"
+    )]
+    pub treasury_top_up_amount: Option<u128>,
+
+    #[arg(
+        long,
+        long_help = "The maximum amount transferred from the treasury in 24 hours.",
+        value_name = "STRK",
+        value_parser = treasury::parse_strk,
+        env = "VALIDATOR_ATTESTATION_TREASURY_DAILY_CAP",
+        requires = "treasury_address"
+    )]
+    pub treasury_daily_cap: Option<u128>,
+
+    #[arg(
+        long,
+        long_help = "Record the transfers from the treasury in a JSON file at PATH, so that the \
+                     daily cap also holds across restarts. The file is created if it does not \
+                     exist.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_TREASURY_TRANSFERS_FILE",
+        requires = "treasury_address"
+    )]
+    pub treasury_transfers_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        long_help = "Record the attestation history of each epoch in an SQLite database at PATH. \
+                     The database is created if it does not exist.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_HISTORY_DATABASE"
+    )]
+    pub history_database: Option<PathBuf>,
 
     #[arg(
         long,
@@ -89,9 +380,48 @@ struct Config {
     )]
     pub metrics_address: String,
 
+    #[arg(
+        long,
+        long_help = "Enable the admin endpoints under '/admin' on the metrics server, requiring \
+                     this token in an 'Authorization: Bearer' header. Prefer setting this in the \
+                     environment or reading it from a file instead of passing it on the command \
+                     line.",
+        value_name = "TOKEN",
+        env = "VALIDATOR_ATTESTATION_ADMIN_TOKEN",
+        hide_env_values = true
+    )]
+    pub admin_token: Option<Secret>,
+
+    #[arg(
+        long,
+        long_help = "Read the token of the admin endpoints from a file.",
+        value_name = "PATH",
+        env = "VALIDATOR_ATTESTATION_ADMIN_TOKEN_FILE"
+    )]
+    pub admin_token_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        long_help = "On TERM or INT, wait at most this long for an attestation pending in the \
+                     open attestation window before exiting. A second signal exits immediately.",
+        default_value = "120",
+        value_name = "SECONDS",
+        env = "VALIDATOR_ATTESTATION_SHUTDOWN_TIMEOUT"
+    )]
+    pub shutdown_timeout: u64,
+
     #[arg(long, default_value = "compact", value_name = "FORMAT")]
     pub log_format: LogFormat,
 
+    #[arg(
+        long,
+        long_help = "Which log messages to show, in the syntax of RUST_LOG, for example \
+                     'info,starknet_validator_attestation=debug'. Defaults to RUST_LOG.",
+        value_name = "FILTER",
+        env = "VALIDATOR_ATTESTATION_LOG_FILTER"
+    )]
+    pub log_filter: Option<String>,
+
     #[arg(
         long,
         long_help = "The median tip value from recent transactions is multiplied by this scaling factor when calculating the transaction tip.",
@@ -107,6 +437,32 @@ struct Config {
         env = "VALIDATOR_ATTESTATION_MINIMUM_TIP"
     )]
     pub minimum_tip: u64,
+
+    /// The effective options, for printing and checking what changed on reload.
+    #[arg(skip)]
+    settings: Vec<config_file::Setting>,
+
+    /// User-defined network profiles from the configuration file.
+    #[arg(skip)]
+    networks: Vec<network::NetworkProfile>,
+}
+
+// Parsed once at startup, the size does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Validate the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    #[command(flatten)]
+    Other(commands::Command),
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigCommand {
+    /// Validate the options and the configuration file without connecting to the node, and print
+    /// the effective configuration with secrets redacted.
+    Check,
 }
 
 #[derive(Clone, clap::ValueEnum)]
@@ -115,36 +471,96 @@ enum LogFormat {
     Json,
 }
 
-const TASK_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+impl Config {
+    /// Parses the command line and the environment, with defaults from the
+    /// configuration file.
+    fn load() -> anyhow::Result<Self> {
+        let args: Vec<_> = std::env::args_os().collect();
+        let mut command = Config::command();
+        let mut table = toml::Table::new();
+        let path = command
+            .clone()
+            .ignore_errors(true)
+            .try_get_matches_from(&args)
+            .ok()
+            .and_then(|matches| matches.get_one::<PathBuf>("config").cloned());
+        if let Some(path) = path {
+            table = config_file::read(&path)?;
+            command = config_file::apply(command, &table)?;
+        }
+        let matches = command.try_get_matches_from(args)?;
+        let mut config = Config::from_arg_matches(&matches)?;
+        config.settings = config_file::settings(&Config::command(), &matches, &table);
+        config.networks = network::parse_profiles(table.get("networks"))?;
+        Ok(config)
+    }
+
+    fn tip_calculation_params(&self) -> tip::TipCalculationParams {
+        tip::TipCalculationParams {
+            tip_boost: self.tip_boost,
+            minimum_tip: self.minimum_tip,
+        }
+    }
 
-const JSON_RPC_API_VERSION_REQUIRED: &str = ">=0.10.0,<0.11.0";
+    fn treasury_policy(&self) -> Option<treasury::TopUpPolicy> {
+        Some(treasury::TopUpPolicy {
+            low_water_mark: self.treasury_low_water_mark?,
+            amount: self.treasury_top_up_amount?,
+            daily_cap: self.treasury_daily_cap?,
+        })
+    }
+
+    fn log_filter(&self) -> anyhow::Result<EnvFilter> {
+        match &self.log_filter {
+            Some(directives) => EnvFilter::try_new(directives).context("Parsing log filter"),
+            None => Ok(EnvFilter::from_default_env()),
+        }
+    }
+}
+
+const TASK_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config = Config::parse();
+    let mut config = Config::load().unwrap_or_else(|error| match error.downcast::<clap::Error>() {
+        Ok(error) => error.exit(),
+        Err(error) => {
+            eprintln!("Error: {error:#}");
+            std::process::exit(2);
+        }
+    });
 
     // Configure rustls crypto provider.
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("rustls crypto provider setup should not fail");
 
+    match config.command.take() {
+        Some(Subcommand::Config(ConfigCommand::Check)) => return check_config(&config),
+        Some(Subcommand::Other(command)) => return command.run(&config.networks).await,
+        None => {}
+    }
+    // Required by clap unless running a subcommand.
+    let staker_operational_address = config
+        .staker_operational_address
+        .context("Missing --staker-operational-address")?;
+    let node_url = config.node_url.clone().context("Missing --node-url")?;
+
     // Set up logging
-    match config.log_format {
-        LogFormat::Compact => {
-            let format = tracing_subscriber::fmt::format().compact();
-            tracing_subscriber::fmt()
-                .event_format(format)
-                .with_env_filter(EnvFilter::from_default_env())
-                .init();
-        }
-        LogFormat::Json => {
-            let format = tracing_subscriber::fmt::format().json();
-            tracing_subscriber::fmt()
-                .event_format(format)
-                .with_env_filter(EnvFilter::from_default_env())
-                .init();
-        }
+    let (log_filter, log_filter_handle) =
+        tracing_subscriber::reload::Layer::new(config.log_filter()?);
+    let log_layer = match config.log_format {
+        LogFormat::Compact => tracing_subscriber::fmt::layer()
+            .event_format(tracing_subscriber::fmt::format().compact())
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .event_format(tracing_subscriber::fmt::format().json())
+            .boxed(),
     };
+    tracing_subscriber::registry()
+        .with(log_filter)
+        .with(log_layer)
+        .init();
 
     tracing::info!("Starting up");
 
@@ -152,41 +568,69 @@ async fn main() -> anyhow::Result<()> {
     let http_client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .build()?;
+
     let client = JsonRpcClient::new(HttpTransport::new_with_client(
-        config.node_url.clone(),
-        http_client,
+        node_url.clone(),
+        http_client.clone(),
     ));
 
     // Check version of JSON-RPC API endpoint
-    let spec_requirements = semver::VersionReq::parse(JSON_RPC_API_VERSION_REQUIRED)
-        .expect("JSON-RPC version requirements should be OK");
-    let spec_version: semver::Version = client
-        .spec_version()
+    let spec_version = rpc_version::check(&client)
         .await
-        .context("Getting spec version of node endpoint")?
-        .parse()
-        .context("Parsing JSON-RPC API specification version")?;
-    if !spec_requirements.matches(&spec_version) {
-        tracing::error!(%spec_version, "Inappropriate version of JSON-RPC API detected. This tool requires 0.10.0, usually served on an URL ending in `v0_10`");
-        return Err(anyhow::anyhow!("Inappropriate JSON-RPC API version"));
-    }
+        .inspect_err(|error| tracing::error!(%error, "Unsupported JSON-RPC API version"))?;
+    tracing::debug!(%spec_version, "JSON-RPC API version checked");
 
-    let tip_calculation_params = tip::TipCalculationParams {
-        tip_boost: config.tip_boost,
-        minimum_tip: config.minimum_tip,
-    };
+    let mut tip_calculation_params = config.tip_calculation_params();
 
     // Set up JSON-RPC client
     let chain_id = client.chain_id().await.context("Getting chain ID")?;
-    let (staking_contract_address, attestation_contract_address) =
-        contract_addresses_from_config(&config, chain_id)?;
-    let strk_contract_address = strk_contract_address_from_chain_id(chain_id)?;
+    let expected_network = config
+        .network
+        .as_deref()
+        .map(|network| network::ExpectedNetwork::resolve(network, &config.networks))
+        .transpose()?;
+    match &expected_network {
+        Some(expected_network) => expected_network.check(chain_id)?,
+        None => tracing::warn!(
+            chain_id=%network::format_chain_id(chain_id),
+            "No expected network set with --network, not checking the chain of the node"
+        ),
+    }
+    // For checking the network again when reconnecting.
+    let provider = client.clone();
+    let contract_addresses = network::ContractAddresses::new(
+        chain_id,
+        &config.networks,
+        config.staking_contract_address,
+        config.attestation_contract_address,
+        config.strk_contract_address,
+    );
+    let staking_contract_address = contract_addresses.staking()?;
+    let attestation_contract_address = contract_addresses.attestation()?;
+    let strk_contract_address = contract_addresses.strk()?;
 
+    let paymaster = match config.paymaster_url.clone() {
+        Some(url) => {
+            let api_key = secret::resolve(
+                "paymaster API key",
+                config.paymaster_api_key.as_ref(),
+                config.paymaster_api_key_file.as_deref(),
+            )?;
+            tracing::info!(url=%secret::redact_url(&url), "Using paymaster");
+            Some(
+                paymaster::Paymaster::new(url, api_key.as_ref())
+                    .context("Creating paymaster client")?,
+            )
+        }
+        None => None,
+    };
     let client = jsonrpc::StarknetRpcClient::new(
         client,
         staking_contract_address,
         attestation_contract_address,
         strk_contract_address,
+        config.signature_format,
+        paymaster,
     );
 
     // Initialize Prometheus metrics
@@ -195,41 +639,79 @@ async fn main() -> anyhow::Result<()> {
         .install_recorder()
         .context("Creating Prometheus metrics recorder")?;
     let addr: std::net::SocketAddr = config.metrics_address.parse()?;
-    metrics_exporter::spawn(addr, prometheus_handle)
+    let (status_tx, status_rx) = tokio::sync::watch::channel(None);
+    let (admin_tx, mut admin_rx) = tokio::sync::mpsc::channel(1);
+    let admin = secret::resolve(
+        "admin token",
+        config.admin_token.as_ref(),
+        config.admin_token_file.as_deref(),
+    )?
+    .map(|token| {
+        tracing::info!("Admin endpoints enabled");
+        admin::router(token, admin_tx)
+    });
+    metrics_exporter::spawn(addr, prometheus_handle, status_rx, admin)
         .await
         .context("Staring metrics exporter")?;
 
     // Set up signer
-    let signer = if config.local_signer {
-        tracing::info!("Using local signer");
-        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(
-            Felt::from_hex(
-                &std::env::var("VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY").expect(
-                    "VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY environment variable should be \
-                     set to the private key",
-                ),
-            )
-            .context("Parsing private key")?,
-        ));
-        signer::AttestationSigner::new_local(signer)
-    } else if let Some(url) = config.remote_signer_url {
-        tracing::info!(%url, "Using remote signer");
-        signer::AttestationSigner::new_remote(url).context("Creating remote signer")?
-    } else {
-        anyhow::bail!("Either local_signer or remote_signer_url must be specified");
+    let mut signer = build_signer(&config)?;
+
+    // Set up treasury
+    let mut treasury = match (config.treasury_address, config.treasury_policy()) {
+        (Some(address), Some(policy)) => {
+            let transfers_path = config
+                .treasury_transfers_file
+                .as_deref()
+                .context("The treasury requires --treasury-transfers-file")?;
+            let private_key = secret::resolve(
+                "treasury private key",
+                config.treasury_private_key.as_ref(),
+                config.treasury_private_key_file.as_deref(),
+            )?
+            .context(
+                "The treasury requires its private key, please set \
+                 VALIDATOR_ATTESTATION_TREASURY_PRIVATE_KEY or \
+                 VALIDATOR_ATTESTATION_TREASURY_PRIVATE_KEY_FILE",
+            )?;
+            // Do not attach the parse error: it may contain parts of the key.
+            let private_key = Felt::from_hex(private_key.expose())
+                .map_err(|_| anyhow::anyhow!("Treasury private key is not a valid hex value"))?;
+            tracing::info!(treasury_address=?address, ?policy, "Using treasury");
+            Some(treasury::Treasury::new(
+                JsonRpcClient::new(HttpTransport::new_with_client(
+                    node_url.clone(),
+                    http_client.clone(),
+                )),
+                LocalWallet::from_signing_key(SigningKey::from_secret_scalar(private_key)),
+                address,
+                chain_id,
+                strk_contract_address,
+                policy,
+                transfers_path,
+            )?)
+        }
+        _ => None,
     };
 
+    // Open attestation history
+    let mut history = config
+        .history_database
+        .as_deref()
+        .map(history::History::open)
+        .transpose()?;
+
     // Set up block and event fetchers
-    let node_websocket_url = match config.node_websocket_url {
+    let node_websocket_url = match config.node_websocket_url.clone() {
         Some(url) => url,
         None => {
             tracing::info!("Using JSON-RPC URL as WebSocket URL");
-            let ws_scheme = match config.node_url.scheme() {
+            let ws_scheme = match node_url.scheme() {
                 "http" => "ws",
                 "https" => "wss",
                 _ => panic!("Unsupported Starknet node URL scheme"),
             };
-            let mut node_websocket_url = config.node_url.clone();
+            let mut node_websocket_url = node_url.clone();
             node_websocket_url
                 .set_scheme(ws_scheme)
                 .map_err(|_| anyhow::anyhow!("Failed to construct WebSocket URL"))?;
@@ -237,6 +719,16 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    // The WebSocket endpoint may be another node than the JSON-RPC one.
+    let network_check = expected_network.map(|expected| network::NetworkCheck {
+        provider: provider.clone(),
+        websocket_url: node_websocket_url.clone(),
+        expected,
+    });
+    if let Some(network_check) = &network_check {
+        network_check.check().await?;
+    }
+
     let (reorg_tx, mut reorg_rx) = tokio::sync::mpsc::channel(10);
 
     let (new_heads_tx, mut new_heads_rx) = tokio::sync::mpsc::channel(10);
@@ -255,14 +747,10 @@ async fn main() -> anyhow::Result<()> {
     ));
 
     // Initialize state
-    let attestation_info = loop {
-        match client
-            .get_attestation_info(config.staker_operational_address)
-            .await
-            .context("Getting attestation info")
-        {
-            Ok(attestation_info) => {
-                break attestation_info;
+    let mut state = loop {
+        match initialize_state(&client, staker_operational_address).await {
+            Ok(state) => {
+                break state;
             }
             Err(error) => {
                 tracing::info!(
@@ -273,71 +761,106 @@ async fn main() -> anyhow::Result<()> {
             }
         }
     };
-    tracing::info!(
-        staker_address=?attestation_info.staker_address,
-        operational_address=?attestation_info.operational_address,
-        stake=%attestation_info.stake,
-        epoch_id=%attestation_info.epoch_id,
-        epoch_start=%attestation_info.current_epoch_starting_block,
-        epoch_length=%attestation_info.epoch_len,
-        attestation_window=%attestation_info.attestation_window,
-        "Current attestation info"
-    );
-    let mut state = state::State::from_attestation_info(attestation_info);
+    if let Some(history) = &history {
+        history.check_staker(state.staker_address())?;
+    }
+    record_history(&mut history, |history| history.record_state(&state));
+    let mut block_times = status::BlockTimes::default();
+    // The latest block header, for retrying without waiting for the next one.
+    let mut latest_block: Option<(u64, Felt)> = None;
+    let mut submission = state::Submission::Enabled;
+    metrics::gauge!("validator_attestation_submission_paused").set(0.0);
+    publish_status(&state, &block_times, submission, &status_tx);
 
     // Initialize operational account balance metric
-    update_operational_balance(&client, config.staker_operational_address).await;
+    let mut runway = runway::Runway::default();
+    if let Some(balance) = update_operational_balance(&client, staker_operational_address).await {
+        runway.record_balance(balance);
+    }
 
     // Handle TERM and INT signals
+    let shutdown_timeout = std::time::Duration::from_secs(config.shutdown_timeout);
     let mut term_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
         .context("Setting up TERM signal handler")?;
     let mut int_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())
         .context("Setting up INT signal handler")?;
+    let mut hup_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .context("Setting up HUP signal handler")?;
+
+    // Set while shutting down waits for the pending attestation.
+    let mut shutdown_deadline: Option<tokio::time::Instant> = None;
 
     loop {
         select! {
             _ = term_signal.recv() => {
-                tracing::info!("Received TERM signal, shutting down");
-                break;
+                tracing::info!("Received TERM signal");
+                if shutdown_now(&state, &block_times, &mut shutdown_deadline, shutdown_timeout) {
+                    break;
+                }
             }
             _ = int_signal.recv() => {
-                tracing::info!("Received INT signal, shutting down");
+                tracing::info!("Received INT signal");
+                if shutdown_now(&state, &block_times, &mut shutdown_deadline, shutdown_timeout) {
+                    break;
+                }
+            }
+            _ = hup_signal.recv() => {
+                tracing::info!("Received HUP signal, reloading configuration");
+                match reload_config(&config, treasury.is_some()) {
+                    Ok((new_config, new_signer, new_log_filter)) => {
+                        tip_calculation_params = new_config.tip_calculation_params();
+                        signer = new_signer;
+                        if let Some(treasury) = &mut treasury
+                            && let Some(policy) = new_config.treasury_policy()
+                        {
+                            treasury.set_policy(policy);
+                        }
+                        if let Err(error) = log_filter_handle.reload(new_log_filter) {
+                            tracing::error!(?error, "Failed to reload log filter");
+                        }
+                        config = new_config;
+                        tracing::info!(
+                            tip_boost=%config.tip_boost,
+                            minimum_tip=%config.minimum_tip,
+                            treasury_policy=?config.treasury_policy(),
+                            "Configuration reloaded"
+                        );
+                    }
+                    Err(error) => {
+                        tracing::error!(error=%format!("{error:#}"), "Failed to reload configuration, keeping the current one");
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(shutdown_deadline.unwrap_or_else(tokio::time::Instant::now)), if shutdown_deadline.is_some() => {
+                tracing::warn!("Timed out waiting for the pending attestation, shutting down");
                 break;
             }
             block_fetcher_result = &mut new_block_fetcher_handle => {
+                if on_wrong_network(&block_fetcher_result) {
+                    tracing::error!(error=?block_fetcher_result, "Node is on the wrong network, stopping");
+                    return block_fetcher_result?;
+                }
                 tracing::error!(error=?block_fetcher_result, "New block fetcher task has exited, restarting");
                 let new_block_fetcher_fut = headers::fetch(node_websocket_url.clone(), new_heads_tx.clone(), reorg_tx.clone());
-                new_block_fetcher_handle = tokio::task::spawn(async move {
-                    tokio::time::sleep(TASK_RESTART_DELAY).await;
-                    new_block_fetcher_fut.await
-                });
+                new_block_fetcher_handle = restart_fetcher(new_block_fetcher_fut, network_check.clone());
             }
             events_fetcher_result = &mut events_fetcher_handle => {
+                if on_wrong_network(&events_fetcher_result) {
+                    tracing::error!(error=?events_fetcher_result, "Node is on the wrong network, stopping");
+                    return events_fetcher_result?;
+                }
                 tracing::error!(error=?events_fetcher_result, "Events fetcher task has exited, restarting");
                 let events_fetcher_fut = events::fetch(node_websocket_url.clone(), attestation_contract_address, events_tx.clone(), reorg_tx.clone());
-                events_fetcher_handle = tokio::task::spawn(async move {
-                    tokio::time::sleep(TASK_RESTART_DELAY).await;
-                    events_fetcher_fut.await
-                });
+                events_fetcher_handle = restart_fetcher(events_fetcher_fut, network_check.clone());
             }
             new_block_header = new_heads_rx.recv() => {
                 match new_block_header {
                     Some(header) => {
                         tracing::debug!("Received new block header: {:?}", header);
                         metrics::gauge!("validator_attestation_starknet_latest_block_number").set(header.block_number as f64);
+                        block_times.record(header.block_number, header.timestamp);
 
-                        let old_state = state.clone();
-                        let result = state.handle_new_block_header(&client, config.staker_operational_address, &signer, &tip_calculation_params, header.block_number, header.block_hash).await;
-                        match result {
-                            Ok(new_state) => {
-                                tracing::debug!(?new_state, "State transition complete");
-                                state = new_state;
-                            },
-                            Err(error) => {
-                                tracing::error!(?error, "Failed to handle new block header");
-                                state = old_state;
-                            }
-                        }
+                        let _ = handle_block(&mut state, &mut latest_block, &client, staker_operational_address, &signer, &tip_calculation_params, submission, header.block_number, header.block_hash, &mut treasury, &mut runway, &mut history).await;
                     },
                     None => tracing::warn!("New block header channel closed"),
                 }
@@ -346,8 +869,10 @@ async fn main() -> anyhow::Result<()> {
                 match event {
                     Some(event) => {
                         tracing::debug!("Received new event: {:?}", event);
+                        let old_state = state.clone();
                         state = state.handle_new_event(event);
                         tracing::debug!(new_state=?state, "State transition complete");
+                        after_transition(&client, staker_operational_address, &old_state, &state, &mut treasury, &mut runway, &mut history).await;
                     },
                     None => tracing::warn!("New event channel closed"),
                 }
@@ -356,22 +881,9 @@ async fn main() -> anyhow::Result<()> {
                 match reorg {
                     Some(reorg) => {
                         tracing::debug!(?reorg, "Received reorg notification, reinitializing");
-                        if let Ok(attestation_info) = client
-                            .get_attestation_info(config.staker_operational_address)
-                            .await
-                            .context("Getting attestation info")
-                        {
-                            tracing::info!(
-                                staker_address=?attestation_info.staker_address,
-                                operational_address=?attestation_info.operational_address,
-                                stake=%attestation_info.stake,
-                                epoch_id=%attestation_info.epoch_id,
-                                epoch_start=%attestation_info.current_epoch_starting_block,
-                                epoch_length=%attestation_info.epoch_len,
-                                attestation_window=%attestation_info.attestation_window,
-                                "Current attestation info"
-                            );
-                            state = state::State::from_attestation_info(attestation_info);
+                        if let Ok(new_state) = initialize_state(&client, staker_operational_address).await {
+                            state = new_state;
+                            record_history(&mut history, |history| history.record_state(&state));
                         } else {
                             tracing::error!("Failed to get attestation info, retrying");
                             tokio::time::sleep(TASK_RESTART_DELAY).await;
@@ -381,6 +893,44 @@ async fn main() -> anyhow::Result<()> {
                     None => tracing::warn!("Reorg channel closed"),
                 }
             }
+            Some(request) = admin_rx.recv() => {
+                let result = match request.action {
+                    admin::Action::Pause => {
+                        tracing::warn!("Pausing attestation submission");
+                        submission = state::Submission::Paused;
+                        metrics::gauge!("validator_attestation_submission_paused").set(1.0);
+                        Ok(())
+                    }
+                    admin::Action::Resume => {
+                        tracing::info!("Resuming attestation submission");
+                        submission = state::Submission::Enabled;
+                        metrics::gauge!("validator_attestation_submission_paused").set(0.0);
+                        Ok(())
+                    }
+                    admin::Action::Reinitialize => {
+                        tracing::info!("Reinitializing on admin request");
+                        initialize_state(&client, staker_operational_address).await.map(|new_state| {
+                            state = new_state;
+                            record_history(&mut history, |history| history.record_state(&state));
+                        })
+                    }
+                    admin::Action::Retry => match (submission, latest_block) {
+                        (state::Submission::Paused, _) => Err(anyhow::anyhow!("Attestation submission is paused")),
+                        (_, None) => Err(anyhow::anyhow!("No block header received yet")),
+                        (state::Submission::Enabled, Some((block_number, block_hash))) => {
+                            tracing::info!(%block_number, "Retrying on admin request");
+                            handle_block(&mut state, &mut latest_block, &client, staker_operational_address, &signer, &tip_calculation_params, submission, block_number, block_hash, &mut treasury, &mut runway, &mut history).await
+                        }
+                    },
+                };
+                let _ = request.reply.send(result);
+            }
+        }
+
+        publish_status(&state, &block_times, submission, &status_tx);
+        if shutdown_deadline.is_some() && status::safe_to_restart(&state, &block_times) {
+            tracing::info!(state=%state.name(), "Attestation no longer pending, shutting down");
+            break;
         }
     }
 
@@ -389,67 +939,379 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn contract_addresses_from_config(config: &Config, chain_id: Felt) -> anyhow::Result<(Felt, Felt)> {
-    const MAINNET_STAKING_CONTRACT_ADDRESS: Felt =
-        felt!("0x00ca1702e64c81d9a07b86bd2c540188d92a2c73cf5cc0e508d949015e7e84a7");
-    const SEPOLIA_STAKING_CONTRACT_ADDRESS: Felt =
-        felt!("0x03745ab04a431fc02871a139be6b93d9260b0ff3e779ad9c8b377183b23109f1");
-
-    let staking_contract_address = config.staking_contract_address.or_else(|| {
-        if chain_id == starknet_rust::core::chain_id::MAINNET {
-            Some(MAINNET_STAKING_CONTRACT_ADDRESS)
-        } else if chain_id == starknet_rust::core::chain_id::SEPOLIA {
-            Some(SEPOLIA_STAKING_CONTRACT_ADDRESS)
-        } else {
-            None
+/// Restarts a WebSocket fetcher after a delay, once the node is confirmed to
+/// be on the expected network again.
+fn restart_fetcher(
+    fetcher: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+    network_check: Option<network::NetworkCheck>,
+) -> tokio::task::JoinHandle<anyhow::Result<()>> {
+    tokio::task::spawn(async move {
+        tokio::time::sleep(TASK_RESTART_DELAY).await;
+        if let Some(network_check) = network_check {
+            network_check
+                .wait_until_confirmed(TASK_RESTART_DELAY)
+                .await?;
         }
-    }).with_context(||
-            format!("Staking contract address is required for chain ID {}, please specify it explicitly", chain_id),
+        fetcher.await
+    })
+}
+
+/// Whether a fetcher stopped because the node is on the wrong network, for
+/// example after the endpoint has been switched to another node.
+fn on_wrong_network(result: &Result<anyhow::Result<()>, tokio::task::JoinError>) -> bool {
+    matches!(result, Ok(Err(error)) if error.is::<network::WrongNetwork>())
+}
+
+/// Validates the configuration as far as possible without connecting to the
+/// node.
+fn check_config(config: &Config) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        config.staker_operational_address.is_some(),
+        "staker_operational_address is required"
+    );
+    anyhow::ensure!(config.node_url.is_some(), "node_url is required");
+    config
+        .metrics_address
+        .parse::<std::net::SocketAddr>()
+        .context("Parsing metrics address")?;
+    config.log_filter()?;
+    if let Some(network) = &config.network {
+        network::ExpectedNetwork::resolve(network, &config.networks)?;
+    }
+    build_signer(config)?;
+    secret::resolve(
+        "paymaster API key",
+        config.paymaster_api_key.as_ref(),
+        config.paymaster_api_key_file.as_deref(),
     )?;
+    secret::resolve(
+        "admin token",
+        config.admin_token.as_ref(),
+        config.admin_token_file.as_deref(),
+    )?;
+    if config.treasury_address.is_some() {
+        secret::resolve(
+            "treasury private key",
+            config.treasury_private_key.as_ref(),
+            config.treasury_private_key_file.as_deref(),
+        )?
+        .context("The treasury requires its private key")?;
+        config
+            .treasury_transfers_file
+            .as_ref()
+            .context("The treasury requires --treasury-transfers-file")?;
+    }
 
-    const MAINNET_ATTESTATION_CONTRACT_ADDRESS: Felt =
-        felt!("0x010398fe631af9ab2311840432d507bf7ef4b959ae967f1507928f5afe888a99");
-    const SEPOLIA_ATTESTATION_CONTRACT_ADDRESS: Felt =
-        felt!("0x3f32e152b9637c31bfcf73e434f78591067a01ba070505ff6ee195642c9acfb");
-
-    let attestation_contract_address = config
-        .attestation_contract_address
-        .or_else(|| {
-            if chain_id == starknet_rust::core::chain_id::MAINNET {
-                Some(MAINNET_ATTESTATION_CONTRACT_ADDRESS)
-            } else if chain_id == starknet_rust::core::chain_id::SEPOLIA {
-                Some(SEPOLIA_ATTESTATION_CONTRACT_ADDRESS)
-            } else {
-                None
-            }
-        })
-        .with_context(||
-            format!("Attestation contract address is required for chain ID {}, please specify it explicitly", chain_id),
+    print!("{}", config_file::render(&config.settings));
+    for profile in &config.networks {
+        print!(
+            "\n[networks.{}]\n{}",
+            profile.name,
+            toml::to_string(profile).context("Formatting network profile")?
+        );
+    }
+    eprintln!("Configuration is valid");
+    Ok(())
+}
+
+/// Loads the configuration again on HUP, rejecting it if it changes options
+/// that require a restart. The top-up policy of the treasury can be changed,
+/// but it can neither be removed while a treasury is running
+/// (`treasury_running`) nor be added when no treasury was set up at startup.
+fn reload_config(
+    config: &Config,
+    treasury_running: bool,
+) -> anyhow::Result<(Config, Box<dyn AttestationSigner>, EnvFilter)> {
+    let new_config = Config::load()?;
+    let changes = config_file::changes_requiring_restart(&config.settings, &new_config.settings);
+    anyhow::ensure!(
+        changes.is_empty(),
+        "Changing {} requires a restart",
+        changes.join(", ")
+    );
+    anyhow::ensure!(
+        new_config.networks == config.networks,
+        "Changing networks requires a restart"
+    );
+    match (treasury_running, new_config.treasury_policy().is_some()) {
+        (true, false) => anyhow::bail!(
+            "The treasury is running, the low water mark, top-up amount and daily cap must stay set"
+        ),
+        (false, true) => anyhow::bail!("Setting up a treasury requires a restart"),
+        _ => {}
+    }
+    let signer = build_signer(&new_config)?;
+    let log_filter = new_config.log_filter()?;
+    Ok((new_config, signer, log_filter))
+}
+
+/// Sets up the signer of attestation transactions.
+fn build_signer(config: &Config) -> anyhow::Result<Box<dyn AttestationSigner>> {
+    let remote_signer_options = signer::RemoteSignerOptions {
+        protocol: config.remote_signer_protocol,
+        timeout: std::time::Duration::from_secs(config.remote_signer_timeout),
+        retries: config.remote_signer_retries,
+        retry_delay: std::time::Duration::from_millis(config.remote_signer_retry_delay),
+        deadline: std::time::Duration::from_secs(config.remote_signer_deadline),
+    };
+    let mut signers: Vec<Arc<dyn AttestationSigner>> = Vec::new();
+    if config.local_signer {
+        tracing::info!("Using local signer");
+        let private_key = secret::resolve(
+            "operational private key",
+            config.operational_private_key.as_ref(),
+            config.operational_private_key_file.as_deref(),
+        )?
+        .context(
+            "The local signer requires the operational private key, please set \
+             VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY or \
+             VALIDATOR_ATTESTATION_OPERATIONAL_PRIVATE_KEY_FILE",
         )?;
+        // Do not attach the parse error: it may contain parts of the key.
+        let private_key = Felt::from_hex(private_key.expose())
+            .map_err(|_| anyhow::anyhow!("Operational private key is not a valid hex value"))?;
+        signers.push(Arc::new(LocalWallet::from_signing_key(
+            SigningKey::from_secret_scalar(private_key),
+        )));
+    } else if !config.remote_signer_url.is_empty() {
+        tracing::info!(endpoints=%config.remote_signer_url.len(), options=?remote_signer_options, "Using remote signer");
+        signers.push(Arc::new(
+            signer::RemoteSigner::new(
+                config.remote_signer_url.clone(),
+                remote_signer_options.clone(),
+            )
+            .context("Creating remote signer")?,
+        ));
+    }
+    for url in &config.multisig_signer_url {
+        signers.push(Arc::new(
+            signer::RemoteSigner::new(vec![url.clone()], remote_signer_options.clone())
+                .context("Creating multisig remote signer")?,
+        ));
+    }
+    let signer: Box<dyn AttestationSigner> = match config.multisig_threshold {
+        Some(threshold) => {
+            tracing::info!(signers=%signers.len(), %threshold, layout=?config.multisig_layout, "Using multisig signer");
+            Box::new(
+                multisig::MultiSigner::new(
+                    signers,
+                    threshold,
+                    config.multisig_layout,
+                    std::time::Duration::from_secs(config.multisig_timeout),
+                )
+                .context("Creating multisig signer")?,
+            )
+        }
+        None => match signers.pop() {
+            Some(signer) => Box::new(signer),
+            None => anyhow::bail!("Either local_signer or remote_signer_url must be specified"),
+        },
+    };
+    Ok(signer)
+}
 
-    Ok((staking_contract_address, attestation_contract_address))
+/// Handles a block header, keeping the current state if that fails.
+#[allow(clippy::too_many_arguments)]
+async fn handle_block<C: Client + Send + Sync + 'static, S: AttestationSigner + ?Sized>(
+    state: &mut state::State,
+    latest_block: &mut Option<(u64, Felt)>,
+    client: &C,
+    operational_address: Felt,
+    signer: &S,
+    tip_calculation_params: &tip::TipCalculationParams,
+    submission: state::Submission,
+    block_number: u64,
+    block_hash: Felt,
+    treasury: &mut Option<treasury::Treasury>,
+    runway: &mut runway::Runway,
+    history: &mut Option<history::History>,
+) -> anyhow::Result<()> {
+    *latest_block = Some((block_number, block_hash));
+    let result = state
+        .clone()
+        .handle_new_block_header(
+            client,
+            operational_address,
+            signer,
+            tip_calculation_params,
+            submission,
+            block_number,
+            block_hash,
+        )
+        .await;
+    match result {
+        Ok(new_state) => {
+            tracing::debug!(?new_state, "State transition complete");
+            after_transition(
+                client,
+                operational_address,
+                state,
+                &new_state,
+                treasury,
+                runway,
+                history,
+            )
+            .await;
+            *state = new_state;
+            Ok(())
+        }
+        Err(error) => {
+            tracing::error!(?error, %block_number, "Failed to handle new block header");
+            Err(error)
+        }
+    }
 }
 
-fn strk_contract_address_from_chain_id(chain_id: Felt) -> anyhow::Result<Felt> {
-    // STRK contract address (same for both mainnet and testnet currently)
-    const STRK_CONTRACT_ADDRESS: Felt =
-        felt!("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d");
+/// Bookkeeping outside of the state machine after a state transition.
+async fn after_transition<C: Client>(
+    client: &C,
+    operational_address: Felt,
+    old_state: &state::State,
+    new_state: &state::State,
+    treasury: &mut Option<treasury::Treasury>,
+    runway: &mut runway::Runway,
+    history: &mut Option<history::History>,
+) {
+    record_history(history, |history| {
+        history.record_transition(old_state, new_state)
+    });
+
+    if new_state.epoch_id() != old_state.epoch_id() {
+        runway.record_epoch_start(new_state.epoch_id(), std::time::Instant::now());
+        if let Some(balance) = update_operational_balance(client, operational_address).await {
+            runway.record_balance(balance);
+            if let Some(treasury) = treasury
+                && let Err(error) = treasury
+                    .top_up_if_needed(operational_address, balance)
+                    .await
+            {
+                tracing::error!(?error, "Failed to top up operational account");
+            }
+        }
+        runway.export();
+    }
 
-    // Support mainnet and sepolia (both use same contract address currently)
-    if chain_id == starknet_rust::core::chain_id::MAINNET
-        || chain_id == starknet_rust::core::chain_id::SEPOLIA
+    // Reverted transactions are charged too, so the fee is recorded whenever a
+    // transaction is no longer waiting for confirmation.
+    if let Some(transaction) = old_state.submitted_transaction()
+        && new_state.submitted_transaction() != Some(transaction)
     {
-        Ok(STRK_CONTRACT_ADDRESS)
-    } else {
-        anyhow::bail!(
-            "STRK contract address is not configured for chain ID {}",
-            chain_id
-        )
+        // Fees of sponsored transactions are paid by the paymaster.
+        if let Some(tip) = transaction.tip {
+            record_attestation_fee(client, transaction.transaction_hash, tip, runway, history)
+                .await;
+        }
+        if let Some(balance) = update_operational_balance(client, operational_address).await {
+            runway.record_balance(balance);
+        }
+        runway.export();
+    }
+}
+
+async fn record_attestation_fee<C: Client>(
+    client: &C,
+    transaction_hash: Felt,
+    tip: u64,
+    runway: &mut runway::Runway,
+    history: &mut Option<history::History>,
+) {
+    metrics::histogram!("validator_attestation_attestation_tip").record(tip as f64);
+    match client.get_transaction_fee(transaction_hash).await {
+        Ok(fee) => {
+            let fee_strk = fee as f64 / 1e18;
+            tracing::info!(?transaction_hash, %fee_strk, %tip, "Attestation fee paid");
+            metrics::gauge!("validator_attestation_fees_paid_strk").increment(fee_strk);
+            metrics::histogram!("validator_attestation_attestation_fee_strk").record(fee_strk);
+            runway.record_fee(fee);
+            record_history(history, |history| history.record_fee(transaction_hash, fee));
+        }
+        Err(error) => {
+            tracing::warn!(
+                ?transaction_hash,
+                ?error,
+                "Failed to get attestation transaction fee"
+            );
+        }
+    }
+}
+
+/// Decides whether to stop on a shutdown signal. While our attestation is
+/// pending in an open attestation window, shutting down waits for it until
+/// `timeout` has elapsed or a second signal arrives.
+fn shutdown_now(
+    state: &state::State,
+    block_times: &status::BlockTimes,
+    shutdown_deadline: &mut Option<tokio::time::Instant>,
+    timeout: std::time::Duration,
+) -> bool {
+    if shutdown_deadline.is_some() {
+        tracing::info!("Received second shutdown signal, shutting down");
+        return true;
+    }
+    if status::safe_to_restart(state, block_times) {
+        tracing::info!("Shutting down");
+        return true;
+    }
+
+    tracing::info!(
+        state=%state.name(),
+        timeout_seconds=%timeout.as_secs(),
+        "Attestation is pending in the open attestation window, waiting for it before shutting down"
+    );
+    *shutdown_deadline = Some(tokio::time::Instant::now() + timeout);
+    false
+}
+
+/// Initializes the state from the current attestation info of the staker.
+async fn initialize_state<C: Client>(
+    client: &C,
+    operational_address: Felt,
+) -> anyhow::Result<state::State> {
+    let attestation_info = client
+        .get_attestation_info(operational_address)
+        .await
+        .context("Getting attestation info")?;
+    tracing::info!(
+        staker_address=?attestation_info.staker_address,
+        operational_address=?attestation_info.operational_address,
+        stake=%attestation_info.stake,
+        epoch_id=%attestation_info.epoch_id,
+        epoch_start=%attestation_info.current_epoch_starting_block,
+        epoch_length=%attestation_info.epoch_len,
+        attestation_window=%attestation_info.attestation_window,
+        "Current attestation info"
+    );
+    Ok(state::State::from_attestation_info(attestation_info))
+}
+
+fn publish_status(
+    state: &state::State,
+    block_times: &status::BlockTimes,
+    submission: state::Submission,
+    status_tx: &tokio::sync::watch::Sender<Option<status::Status>>,
+) {
+    let status = status::Status::new(state, block_times, submission);
+    status.export();
+    status_tx.send_replace(Some(status));
+}
+
+/// Writing the history is best effort: a failure must not stop attesting.
+fn record_history(
+    history: &mut Option<history::History>,
+    record: impl FnOnce(&mut history::History) -> anyhow::Result<()>,
+) {
+    if let Some(history) = history
+        && let Err(error) = record(history)
+    {
+        tracing::error!(?error, "Failed to record attestation history");
     }
 }
 
 // Helper function to update operational account balance
-async fn update_operational_balance<C: Client>(client: &C, operational_address: Felt) {
+async fn update_operational_balance<C: Client>(
+    client: &C,
+    operational_address: Felt,
+) -> Option<u128> {
     match client.get_strk_balance(operational_address).await {
         Ok(balance) => {
             // Convert to floating point STRK (divide by 10^18)
@@ -457,9 +1319,11 @@ async fn update_operational_balance<C: Client>(client: &C, operational_address:
             metrics::gauge!("validator_attestation_operational_account_balance_strk")
                 .set(balance_strk);
             tracing::debug!(%balance_strk, "Updated operational account balance");
+            Some(balance)
         }
         Err(err) => {
             tracing::warn!(error=%err, "Failed to get operational account STRK balance");
+            None
         }
     }
 }
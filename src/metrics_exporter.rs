@@ -1,19 +1,40 @@
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
 use metrics_exporter_prometheus::PrometheusHandle;
+use tokio::sync::watch;
+
+use crate::status::Status;
 
 #[derive(Clone)]
 struct State {
     prometheus_handle: PrometheusHandle,
+    status: watch::Receiver<Option<Status>>,
 }
 
 pub async fn spawn(
     addr: impl Into<std::net::SocketAddr> + 'static,
     prometheus_handle: PrometheusHandle,
+    status: watch::Receiver<Option<Status>>,
+    admin: Option<axum::Router>,
 ) -> anyhow::Result<tokio::task::JoinHandle<()>> {
     describe_metrics();
 
     let app = axum::Router::new()
         .route("/metrics", axum::routing::get(metrics_route))
-        .with_state(State { prometheus_handle });
+        .route("/status", axum::routing::get(status_route))
+        .route("/schedule.ics", axum::routing::get(schedule_route))
+        .route(
+            "/safe-to-restart",
+            axum::routing::get(safe_to_restart_route),
+        )
+        .with_state(State {
+            prometheus_handle,
+            status,
+        })
+        .merge(admin.unwrap_or_default());
     let listener = tokio::net::TcpListener::bind(addr.into()).await?;
     let handle = tokio::task::spawn(async move {
         axum::serve(listener, app.into_make_service())
@@ -28,6 +49,54 @@ async fn metrics_route(axum::extract::State(state): axum::extract::State<State>)
     state.prometheus_handle.render()
 }
 
+async fn status_route(axum::extract::State(state): axum::extract::State<State>) -> Response {
+    match state.status.borrow().as_ref() {
+        Some(status) => Json(status).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "Starting up").into_response(),
+    }
+}
+
+async fn schedule_route(axum::extract::State(state): axum::extract::State<State>) -> Response {
+    match state.status.borrow().as_ref() {
+        Some(status) => (
+            [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+            status.to_ical(chrono::Utc::now()),
+        )
+            .into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "Starting up").into_response(),
+    }
+}
+
+/// 200 if restarting now does not interrupt a pending attestation, 503
+/// otherwise.
+async fn safe_to_restart_route(
+    axum::extract::State(state): axum::extract::State<State>,
+) -> Response {
+    match state.status.borrow().as_ref() {
+        Some(status) => {
+            let status_code = if status.safe_to_restart {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            (
+                status_code,
+                Json(serde_json::json!({
+                    "safe_to_restart": status.safe_to_restart,
+                    "state": status.state,
+                })),
+            )
+                .into_response()
+        }
+        // Nothing to interrupt while starting up.
+        None => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "safe_to_restart": true })),
+        )
+            .into_response(),
+    }
+}
+
 fn describe_metrics() {
     // Starknet
     let _ = metrics::gauge!("validator_attestation_starknet_latest_block_number");
@@ -106,4 +175,91 @@ fn describe_metrics() {
         metrics::Unit::Count,
         "Number of total attestation confirmations observed"
     );
+
+    // Remote signer
+    metrics::describe_histogram!(
+        "validator_attestation_remote_signer_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "Duration of requests to the remote signer, by endpoint"
+    );
+    metrics::describe_counter!(
+        "validator_attestation_remote_signer_error_count",
+        metrics::Unit::Count,
+        "Number of failed requests to the remote signer, by endpoint"
+    );
+
+    // Paymaster
+    metrics::describe_counter!(
+        "validator_attestation_paymaster_sponsored_count",
+        metrics::Unit::Count,
+        "Number of attestations sponsored by the paymaster"
+    );
+    metrics::describe_counter!(
+        "validator_attestation_paymaster_fallback_count",
+        metrics::Unit::Count,
+        "Number of attestations sent as self-paid transactions because the paymaster refused to sponsor them"
+    );
+
+    // Fees
+    let _ = metrics::gauge!("validator_attestation_fees_paid_strk");
+    metrics::describe_gauge!(
+        "validator_attestation_fees_paid_strk",
+        metrics::Unit::Count,
+        "Total actual fee paid in STRK for confirmed and reverted attestations since startup"
+    );
+    metrics::describe_histogram!(
+        "validator_attestation_attestation_fee_strk",
+        metrics::Unit::Count,
+        "Actual fee paid in STRK per confirmed or reverted attestation"
+    );
+    metrics::describe_histogram!(
+        "validator_attestation_attestation_tip",
+        metrics::Unit::Count,
+        "Tip per L2 gas unit (in FRI) of confirmed or reverted attestations"
+    );
+
+    // Schedule
+    metrics::describe_gauge!(
+        "validator_attestation_predicted_block_timestamp_seconds",
+        metrics::Unit::Seconds,
+        "Estimated time of the block to attest (block=\"block_to_attest\"), and the start \
+         (block=\"window_start\") and end (block=\"window_end\") of the attestation window of \
+         the current (epoch=\"current\") and next (epoch=\"next\") epoch"
+    );
+
+    // Runway
+    metrics::describe_gauge!(
+        "validator_attestation_epochs_remaining",
+        metrics::Unit::Count,
+        "Number of epochs the operational account balance covers at the average attestation fee"
+    );
+    metrics::describe_gauge!(
+        "validator_attestation_estimated_runway_seconds",
+        metrics::Unit::Seconds,
+        "Estimated time until the operational account balance runs out"
+    );
+
+    // Admin
+    let _ = metrics::gauge!("validator_attestation_submission_paused");
+    metrics::describe_gauge!(
+        "validator_attestation_submission_paused",
+        "1 while sending attestation transactions is paused on the admin API"
+    );
+    metrics::describe_counter!(
+        "validator_attestation_admin_action_count",
+        metrics::Unit::Count,
+        "Number of actions requested on the admin API, by action and result"
+    );
+
+    // Treasury
+    metrics::describe_counter!(
+        "validator_attestation_treasury_top_up_count",
+        metrics::Unit::Count,
+        "Number of top-up transfers sent from the treasury to the operational account"
+    );
+    metrics::describe_counter!(
+        "validator_attestation_treasury_top_up_failure_count",
+        metrics::Unit::Count,
+        "Number of top-up transfers from the treasury that failed to be sent"
+    );
 }
@@ -0,0 +1,419 @@
+use std::{sync::Arc, time::Duration};
+
+use starknet_rust::core::types::Felt;
+use starknet_rust::signers::SignerInteractivityContext;
+use tokio::sync::OnceCell;
+
+use crate::signer::{AttestationSigner, SignError, SigningRequest};
+
+/// How the signatures of the individual signers are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum MultisigLayout {
+    /// `[r1, s1, r2, s2, ...]` in the order the signers were configured.
+    #[default]
+    Concatenated,
+    /// The `Array<SignerSignature>` layout of Argent multisig accounts:
+    /// `[n, 0, public_key1, r1, s1, 0, public_key2, r2, s2, ...]`, sorted by
+    /// signer GUID.
+    Argent,
+}
+
+/// `'Starknet Signer'`, the type tag of Starknet signers in Argent accounts.
+const STARKNET_SIGNER_TYPE: Felt = Felt::from_hex_unchecked("0x537461726b6e6574205369676e6572");
+
+/// The GUID identifying a Starknet signer in Argent accounts, which expect
+/// signer signatures in ascending GUID order.
+fn argent_signer_guid(public_key: Felt) -> Felt {
+    starknet_rust_crypto::poseidon_hash(STARKNET_SIGNER_TYPE, public_key)
+}
+
+/// A signature collected from one of the signers.
+struct Collected {
+    index: usize,
+    signature: Vec<Felt>,
+    /// Only fetched if the layout needs it.
+    public_key: Option<Felt>,
+}
+
+/// A signer collecting signatures from several signers guarding a multisig
+/// operational account.
+///
+/// Signing requests are sent to all signers concurrently, and signing succeeds
+/// as soon as `threshold` of them have returned a signature.
+#[derive(Debug)]
+pub struct MultiSigner {
+    signers: Vec<Arc<dyn AttestationSigner>>,
+    public_keys: Vec<Arc<OnceCell<Felt>>>,
+    threshold: usize,
+    layout: MultisigLayout,
+    timeout: Duration,
+}
+
+impl MultiSigner {
+    pub fn new(
+        signers: Vec<Arc<dyn AttestationSigner>>,
+        threshold: usize,
+        layout: MultisigLayout,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(threshold > 0, "Multisig threshold must be at least 1");
+        anyhow::ensure!(
+            threshold <= signers.len(),
+            "Multisig threshold {threshold} is larger than the number of signers ({})",
+            signers.len()
+        );
+
+        Ok(Self {
+            public_keys: signers.iter().map(|_| Arc::default()).collect(),
+            signers,
+            threshold,
+            layout,
+            timeout,
+        })
+    }
+
+    /// Collects signatures until the threshold is met, returning them in the
+    /// order of the signers.
+    ///
+    /// Public keys needed by the layout are fetched, and cached, along with the
+    /// signatures, so that `timeout` bounds the whole operation.
+    async fn collect(&self, request: &SigningRequest) -> Result<Vec<Collected>, SignError> {
+        let request = Arc::new(request.clone());
+        let mut pending = tokio::task::JoinSet::new();
+        for (index, signer) in self.signers.iter().enumerate() {
+            let signer = Arc::clone(signer);
+            let request = Arc::clone(&request);
+            let public_key = (self.layout == MultisigLayout::Argent)
+                .then(|| Arc::clone(&self.public_keys[index]));
+            pending.spawn(async move {
+                let result = async {
+                    let signature = signer.sign(&request).await?;
+                    let public_key = match public_key {
+                        Some(cell) => Some(*cell.get_or_try_init(|| signer.public_key()).await?),
+                        None => None,
+                    };
+                    Ok::<_, SignError>(Collected {
+                        index,
+                        signature,
+                        public_key,
+                    })
+                };
+                (index, result.await)
+            });
+        }
+
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let mut signatures = Vec::with_capacity(self.threshold);
+        while signatures.len() < self.threshold {
+            match tokio::time::timeout_at(deadline, pending.join_next()).await {
+                Ok(Some(Ok((_, Ok(collected))))) => signatures.push(collected),
+                Ok(Some(Ok((index, Err(error))))) => {
+                    tracing::warn!(%index, %error, "Multisig signer failed to sign");
+                }
+                Ok(Some(Err(error))) => {
+                    tracing::error!(%error, "Multisig signer task failed");
+                }
+                // All signers have responded, or the timeout has elapsed.
+                Ok(None) | Err(_) => break,
+            }
+        }
+        // Remaining signers are no longer needed.
+        pending.abort_all();
+
+        if signatures.len() < self.threshold {
+            return Err(SignError::ThresholdNotMet {
+                collected: signatures.len(),
+                threshold: self.threshold,
+            });
+        }
+
+        signatures.sort_by_key(|collected| collected.index);
+        Ok(signatures)
+    }
+}
+
+#[async_trait::async_trait]
+impl AttestationSigner for MultiSigner {
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+        let signatures = self.collect(request).await?;
+
+        match self.layout {
+            MultisigLayout::Concatenated => Ok(signatures
+                .into_iter()
+                .flat_map(|collected| collected.signature)
+                .collect()),
+            MultisigLayout::Argent => {
+                let mut signer_signatures = Vec::with_capacity(signatures.len());
+                for Collected {
+                    index,
+                    signature,
+                    public_key,
+                } in signatures
+                {
+                    let (&[r, s], Some(public_key)) = (&signature[..], public_key) else {
+                        return Err(SignError::InvalidSignature(format!(
+                            "Expected an [r, s] signature from signer {index}"
+                        )));
+                    };
+                    signer_signatures.push((public_key, r, s));
+                }
+                signer_signatures.sort_by_key(|(public_key, _, _)| argent_signer_guid(*public_key));
+
+                let mut signature = vec![signer_signatures.len().into()];
+                for (public_key, r, s) in signer_signatures {
+                    // Variant index of `SignerSignature::Starknet`.
+                    signature.extend([Felt::ZERO, public_key, r, s]);
+                }
+                Ok(signature)
+            }
+        }
+    }
+
+    async fn public_key(&self) -> Result<Felt, SignError> {
+        Err(SignError::Unsupported(
+            "a multisig signer has no single public key",
+        ))
+    }
+
+    fn is_interactive(&self, context: SignerInteractivityContext<'_>) -> bool {
+        self.signers
+            .iter()
+            .any(|signer| signer.is_interactive(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::core::types::{
+        BroadcastedInvokeTransactionV3, DataAvailabilityMode, ResourceBounds, ResourceBoundsMapping,
+    };
+    use starknet_rust::macros::felt;
+    use starknet_rust::signers::{LocalWallet, SigningKey};
+
+    use crate::signer::AttestationContext;
+
+    use super::*;
+
+    const TRANSACTION_HASH: Felt =
+        felt!("0x382a7406fe3931ba1faf00d1eaa36b7c8770b8d185b091b730ecdb4dba5f3ce");
+
+    /// A signer that never responds.
+    #[derive(Debug)]
+    struct UnresponsiveSigner;
+
+    #[async_trait::async_trait]
+    impl AttestationSigner for UnresponsiveSigner {
+        async fn sign(&self, _request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+            std::future::pending().await
+        }
+
+        async fn public_key(&self) -> Result<Felt, SignError> {
+            std::future::pending().await
+        }
+
+        fn is_interactive(&self, _context: SignerInteractivityContext<'_>) -> bool {
+            true
+        }
+    }
+
+    fn local_signer(private_key: Felt) -> (Arc<dyn AttestationSigner>, SigningKey) {
+        let signing_key = SigningKey::from_secret_scalar(private_key);
+        (
+            Arc::new(LocalWallet::from_signing_key(signing_key.clone())),
+            signing_key,
+        )
+    }
+
+    fn request() -> SigningRequest {
+        let no_resources = ResourceBounds {
+            max_amount: 0,
+            max_price_per_unit: 0,
+        };
+        SigningRequest {
+            transaction_hash: TRANSACTION_HASH,
+            transaction: BroadcastedInvokeTransactionV3 {
+                sender_address: felt!("0xfeedbeef"),
+                calldata: vec![],
+                signature: vec![],
+                nonce: Felt::ZERO,
+                resource_bounds: ResourceBoundsMapping {
+                    l1_gas: no_resources.clone(),
+                    l1_data_gas: no_resources.clone(),
+                    l2_gas: no_resources,
+                },
+                tip: 0,
+                paymaster_data: vec![],
+                account_deployment_data: vec![],
+                nonce_data_availability_mode: DataAvailabilityMode::L1,
+                fee_data_availability_mode: DataAvailabilityMode::L1,
+                is_query: false,
+            },
+            chain_id: Felt::ZERO,
+            context: AttestationContext {
+                staker_address: felt!("0xdeadbeef"),
+                epoch_id: 1,
+                block_number: 2,
+                block_hash: felt!("0x123456789abcdef"),
+                attestation_contract_address: felt!("0xabcdef"),
+            },
+        }
+    }
+
+    fn verify(key: &SigningKey, r: Felt, s: Felt) -> bool {
+        starknet_rust::core::crypto::ecdsa_verify(
+            &key.verifying_key().scalar(),
+            &TRANSACTION_HASH,
+            &starknet_rust::core::crypto::Signature { r, s },
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn concatenated_layout() {
+        let (first, first_key) = local_signer(felt!("0x1234"));
+        let (second, second_key) = local_signer(felt!("0x5678"));
+        let signer = MultiSigner::new(
+            vec![first, Arc::new(UnresponsiveSigner), second],
+            2,
+            MultisigLayout::Concatenated,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        let signature = signer.sign(&request()).await.unwrap();
+
+        assert_eq!(signature.len(), 4);
+        assert!(verify(&first_key, signature[0], signature[1]));
+        assert!(verify(&second_key, signature[2], signature[3]));
+    }
+
+    #[test]
+    fn argent_signer_guid_is_poseidon_2_of_type_and_public_key() {
+        // `poseidon_2(a, b)` of the Argent contracts is the first element of
+        // `hades_permutation(a, b, 2)`.
+        let public_key = felt!("0x1ef15c18599971b7beced415a40f0c7deacfd9b0d1819e03d723d8bc943cfca");
+        let mut state = [
+            starknet_rust::core::utils::cairo_short_string_to_felt("Starknet Signer").unwrap(),
+            public_key,
+            Felt::TWO,
+        ];
+        starknet_rust_crypto::poseidon_permute_comp(&mut state);
+
+        assert_eq!(argent_signer_guid(public_key), state[0]);
+    }
+
+    #[tokio::test]
+    async fn argent_layout_is_sorted_by_signer_guid() {
+        let (first, first_key) = local_signer(felt!("0x1234"));
+        let (second, second_key) = local_signer(felt!("0x5678"));
+        let (third, third_key) = local_signer(felt!("0x9abc"));
+        let signer = MultiSigner::new(
+            vec![first, second, third],
+            3,
+            MultisigLayout::Argent,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        let signature = signer.sign(&request()).await.unwrap();
+
+        let mut keys = [first_key, second_key, third_key];
+        let public_key_order: Vec<_> = {
+            let mut public_keys: Vec<_> = keys
+                .iter()
+                .map(|key| key.verifying_key().scalar())
+                .collect();
+            public_keys.sort();
+            public_keys
+        };
+        keys.sort_by_key(|key| argent_signer_guid(key.verifying_key().scalar()));
+        // The keys are chosen so that the orders differ.
+        assert_ne!(
+            keys.iter()
+                .map(|key| key.verifying_key().scalar())
+                .collect::<Vec<_>>(),
+            public_key_order
+        );
+        assert_eq!(signature.len(), 13);
+        assert_eq!(signature[0], Felt::THREE);
+        for (key, signer_signature) in keys.iter().zip(signature[1..].chunks(4)) {
+            assert_eq!(signer_signature[0], Felt::ZERO);
+            assert_eq!(signer_signature[1], key.verifying_key().scalar());
+            assert!(verify(key, signer_signature[2], signer_signature[3]));
+        }
+    }
+
+    #[tokio::test]
+    async fn threshold_not_met() {
+        let (first, _) = local_signer(felt!("0x1234"));
+        let signer = MultiSigner::new(
+            vec![first, Arc::new(UnresponsiveSigner)],
+            2,
+            MultisigLayout::Concatenated,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+
+        assert_matches::assert_matches!(
+            signer.sign(&request()).await,
+            Err(SignError::ThresholdNotMet {
+                collected: 1,
+                threshold: 2
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn public_key_fetch_is_bounded_by_timeout() {
+        /// A signer that signs, but never returns its public key.
+        #[derive(Debug)]
+        struct KeylessSigner(Arc<dyn AttestationSigner>);
+
+        #[async_trait::async_trait]
+        impl AttestationSigner for KeylessSigner {
+            async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+                self.0.sign(request).await
+            }
+
+            async fn public_key(&self) -> Result<Felt, SignError> {
+                std::future::pending().await
+            }
+
+            fn is_interactive(&self, _context: SignerInteractivityContext<'_>) -> bool {
+                true
+            }
+        }
+
+        let (first, _) = local_signer(felt!("0x1234"));
+        let signer = MultiSigner::new(
+            vec![Arc::new(KeylessSigner(first))],
+            1,
+            MultisigLayout::Argent,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+
+        assert_matches::assert_matches!(
+            signer.sign(&request()).await,
+            Err(SignError::ThresholdNotMet {
+                collected: 0,
+                threshold: 1
+            })
+        );
+    }
+
+    #[test]
+    fn threshold_larger_than_signer_count() {
+        let (first, _) = local_signer(felt!("0x1234"));
+
+        assert!(
+            MultiSigner::new(
+                vec![first],
+                2,
+                MultisigLayout::Concatenated,
+                Duration::from_secs(1)
+            )
+            .is_err()
+        );
+    }
+}
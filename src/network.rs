@@ -0,0 +1,469 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use starknet_rust::{
+    core::{
+        chain_id,
+        types::Felt,
+        utils::{cairo_short_string_to_felt, parse_cairo_short_string},
+    },
+    macros::felt,
+    providers::{JsonRpcClient, Provider, jsonrpc::HttpTransport},
+};
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+const MAINNET_STAKING_CONTRACT_ADDRESS: Felt =
+    felt!("0x00ca1702e64c81d9a07b86bd2c540188d92a2c73cf5cc0e508d949015e7e84a7");
+const MAINNET_ATTESTATION_CONTRACT_ADDRESS: Felt =
+    felt!("0x010398fe631af9ab2311840432d507bf7ef4b959ae967f1507928f5afe888a99");
+const SEPOLIA_STAKING_CONTRACT_ADDRESS: Felt =
+    felt!("0x03745ab04a431fc02871a139be6b93d9260b0ff3e779ad9c8b377183b23109f1");
+const SEPOLIA_ATTESTATION_CONTRACT_ADDRESS: Felt =
+    felt!("0x3f32e152b9637c31bfcf73e434f78591067a01ba070505ff6ee195642c9acfb");
+// Same on mainnet and Sepolia.
+const STRK_CONTRACT_ADDRESS: Felt =
+    felt!("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d");
+
+/// The contract addresses on a chain.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkProfile {
+    #[serde(skip)]
+    pub name: String,
+    /// A Cairo short string such as `SN_MAIN`, or a hex value.
+    #[serde(with = "chain_id_format")]
+    pub chain_id: Felt,
+    pub staking_contract_address: Option<Felt>,
+    pub attestation_contract_address: Option<Felt>,
+    pub strk_contract_address: Option<Felt>,
+}
+
+/// The profiles of the networks with known deployments.
+pub fn built_in() -> [NetworkProfile; 2] {
+    [
+        NetworkProfile {
+            name: "mainnet".to_owned(),
+            chain_id: chain_id::MAINNET,
+            staking_contract_address: Some(MAINNET_STAKING_CONTRACT_ADDRESS),
+            attestation_contract_address: Some(MAINNET_ATTESTATION_CONTRACT_ADDRESS),
+            strk_contract_address: Some(STRK_CONTRACT_ADDRESS),
+        },
+        NetworkProfile {
+            name: "sepolia".to_owned(),
+            chain_id: chain_id::SEPOLIA,
+            staking_contract_address: Some(SEPOLIA_STAKING_CONTRACT_ADDRESS),
+            attestation_contract_address: Some(SEPOLIA_ATTESTATION_CONTRACT_ADDRESS),
+            strk_contract_address: Some(STRK_CONTRACT_ADDRESS),
+        },
+    ]
+}
+
+/// Parses the `[networks.<name>]` tables of the configuration file.
+pub fn parse_profiles(networks: Option<&toml::Value>) -> anyhow::Result<Vec<NetworkProfile>> {
+    let Some(networks) = networks else {
+        return Ok(Vec::new());
+    };
+    let networks = networks
+        .as_table()
+        .context("networks in the configuration file must be a table")?;
+    networks
+        .iter()
+        .map(|(name, profile)| {
+            let profile: NetworkProfile = profile
+                .clone()
+                .try_into()
+                .with_context(|| format!("Parsing network profile {name}"))?;
+            Ok(NetworkProfile {
+                name: name.clone(),
+                ..profile
+            })
+        })
+        .collect()
+}
+
+/// The network the node is expected to be on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectedNetwork {
+    pub name: String,
+    pub chain_id: Felt,
+}
+
+impl ExpectedNetwork {
+    /// Resolves the name of a user-defined or built-in network profile, or a
+    /// chain ID.
+    pub fn resolve(network: &str, profiles: &[NetworkProfile]) -> anyhow::Result<Self> {
+        let built_in = built_in();
+        let chain_id = match profiles
+            .iter()
+            .chain(&built_in)
+            .find(|profile| profile.name == network)
+        {
+            Some(profile) => profile.chain_id,
+            None => parse_chain_id(network).with_context(|| {
+                format!("Network {network} is neither a network profile nor a chain ID")
+            })?,
+        };
+        Ok(Self {
+            name: network.to_owned(),
+            chain_id,
+        })
+    }
+
+    pub fn check(&self, chain_id: Felt) -> Result<(), WrongNetwork> {
+        if chain_id == self.chain_id {
+            Ok(())
+        } else {
+            Err(WrongNetwork {
+                chain_id,
+                expected: self.clone(),
+            })
+        }
+    }
+}
+
+/// The node is not on the expected network.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "The node is on chain {}, but the expected network {} is chain {}",
+    format_chain_id(*.chain_id),
+    .expected.name,
+    format_chain_id(.expected.chain_id)
+)]
+pub struct WrongNetwork {
+    pub chain_id: Felt,
+    pub expected: ExpectedNetwork,
+}
+
+/// Checks that both the JSON-RPC and the WebSocket endpoints of the node are
+/// on the expected network.
+#[derive(Clone, Debug)]
+pub struct NetworkCheck {
+    pub provider: JsonRpcClient<HttpTransport>,
+    pub websocket_url: Url,
+    pub expected: ExpectedNetwork,
+}
+
+impl NetworkCheck {
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let chain_id = self.provider.chain_id().await.context("Getting chain ID")?;
+        self.expected.check(chain_id)?;
+        let chain_id = websocket_chain_id(&self.websocket_url)
+            .await
+            .context("Getting chain ID over WebSocket")?;
+        self.expected.check(chain_id)?;
+        Ok(())
+    }
+
+    /// Retries the check until the chain of the node is known, for example
+    /// while the node is restarting. Fails only if it is on the wrong network.
+    pub async fn wait_until_confirmed(&self, retry_delay: Duration) -> anyhow::Result<()> {
+        loop {
+            match self.check().await {
+                Ok(()) => return Ok(()),
+                Err(error) if error.is::<WrongNetwork>() => return Err(error),
+                Err(error) => {
+                    tracing::warn!(
+                        error=%format!("{error:#}"),
+                        "Failed to check the network of the node, retrying"
+                    );
+                    tokio::time::sleep(retry_delay).await;
+                }
+            }
+        }
+    }
+}
+
+const WEBSOCKET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Calls `starknet_chainId` over a WebSocket connection: the subscription
+/// client does not support other methods.
+pub async fn websocket_chain_id(url: &Url) -> anyhow::Result<Felt> {
+    tokio::time::timeout(WEBSOCKET_TIMEOUT, async {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "starknet_chainId",
+            "params": [],
+        });
+        socket.send(Message::text(request.to_string())).await?;
+        while let Some(message) = socket.next().await {
+            let Message::Text(text) = message? else {
+                continue;
+            };
+            let response: serde_json::Value =
+                serde_json::from_str(&text).context("Parsing JSON-RPC response")?;
+            // Skip notifications of other subscriptions.
+            if response["id"] != 1 {
+                continue;
+            }
+            let _ = socket.close(None).await;
+            let chain_id = response["result"]
+                .as_str()
+                .with_context(|| format!("Unexpected response {response}"))?;
+            return Felt::from_hex(chain_id).context("Parsing chain ID");
+        }
+        anyhow::bail!("Connection closed before the response")
+    })
+    .await
+    .context("Timed out")?
+}
+
+/// The chain ID as a Cairo short string, if possible.
+pub fn format_chain_id(chain_id: Felt) -> String {
+    parse_cairo_short_string(&chain_id).unwrap_or_else(|_| format!("{chain_id:#x}"))
+}
+
+/// The contract addresses on a chain: those given explicitly, or else those of
+/// the first user-defined or built-in network profile of the chain.
+#[derive(Clone, Debug)]
+pub struct ContractAddresses {
+    chain_id: Felt,
+    staking: Option<Felt>,
+    attestation: Option<Felt>,
+    strk: Option<Felt>,
+}
+
+impl ContractAddresses {
+    pub fn new(
+        chain_id: Felt,
+        profiles: &[NetworkProfile],
+        staking: Option<Felt>,
+        attestation: Option<Felt>,
+        strk: Option<Felt>,
+    ) -> Self {
+        let built_in = built_in();
+        let profile = profiles
+            .iter()
+            .chain(&built_in)
+            .find(|profile| profile.chain_id == chain_id);
+        if let Some(profile) = profile {
+            tracing::debug!(network=%profile.name, "Using network profile");
+        }
+        Self {
+            chain_id,
+            staking: staking.or(profile.and_then(|profile| profile.staking_contract_address)),
+            attestation: attestation
+                .or(profile.and_then(|profile| profile.attestation_contract_address)),
+            strk: strk.or(profile.and_then(|profile| profile.strk_contract_address)),
+        }
+    }
+
+    pub fn staking(&self) -> anyhow::Result<Felt> {
+        self.required("Staking", self.staking)
+    }
+
+    pub fn attestation(&self) -> anyhow::Result<Felt> {
+        self.required("Attestation", self.attestation)
+    }
+
+    pub fn strk(&self) -> anyhow::Result<Felt> {
+        self.required("STRK", self.strk)
+    }
+
+    fn required(&self, contract: &str, address: Option<Felt>) -> anyhow::Result<Felt> {
+        address.with_context(|| {
+            format!(
+                "{contract} contract address is required for chain ID {}, please specify it \
+                 explicitly or in a network profile",
+                format_chain_id(self.chain_id)
+            )
+        })
+    }
+}
+
+mod chain_id_format {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use starknet_rust::core::types::Felt;
+
+    pub fn serialize<S: Serializer>(chain_id: &Felt, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_chain_id(*chain_id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Felt, D::Error> {
+        let chain_id = String::deserialize(deserializer)?;
+        super::parse_chain_id(&chain_id).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses a chain ID given as a Cairo short string, such as `SN_MAIN`, or as a
+/// hex value.
+pub fn parse_chain_id(chain_id: &str) -> anyhow::Result<Felt> {
+    if chain_id.starts_with("0x") {
+        Felt::from_hex(chain_id).context("Parsing chain ID")
+    } else {
+        cairo_short_string_to_felt(chain_id).context("Parsing chain ID")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// A WebSocket endpoint answering `starknet_chainId` with `chain_id`, after
+    /// an unrelated notification.
+    async fn spawn_websocket_node(listener: TcpListener, chain_id: Felt) {
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    let Some(Ok(Message::Text(request))) = socket.next().await else {
+                        return;
+                    };
+                    let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+                    assert_eq!(request["method"], "starknet_chainId");
+                    let notification = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "starknet_subscriptionNewHeads",
+                        "params": {},
+                    });
+                    let response = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": format!("{chain_id:#x}"),
+                    });
+                    for message in [notification, response] {
+                        let _ = socket.send(Message::text(message.to_string())).await;
+                    }
+                });
+            }
+        });
+    }
+
+    async fn spawn_http_node(chain_id: Felt) -> Url {
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(
+                move |axum::Json(request): axum::Json<serde_json::Value>| async move {
+                    axum::Json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": format!("{chain_id:#x}"),
+                    }))
+                },
+            ),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    fn network_check(http_url: Url, websocket_url: Url) -> NetworkCheck {
+        NetworkCheck {
+            provider: JsonRpcClient::new(HttpTransport::new(http_url)),
+            websocket_url,
+            expected: ExpectedNetwork::resolve("mainnet", &[]).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn websocket_endpoint_on_the_wrong_network() {
+        let http_url = spawn_http_node(chain_id::MAINNET).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let websocket_url =
+            Url::parse(&format!("ws://{}/", listener.local_addr().unwrap())).unwrap();
+        spawn_websocket_node(listener, chain_id::SEPOLIA).await;
+
+        assert_eq!(
+            websocket_chain_id(&websocket_url).await.unwrap(),
+            chain_id::SEPOLIA
+        );
+        let error = network_check(http_url, websocket_url)
+            .wait_until_confirmed(Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(error.is::<WrongNetwork>(), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoint_is_retried() {
+        let http_url = spawn_http_node(chain_id::MAINNET).await;
+        // Reserve a port, but only accept connections on it later.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let websocket_url = Url::parse(&format!("ws://{address}/")).unwrap();
+
+        let check = network_check(http_url, websocket_url);
+        let confirmed =
+            tokio::spawn(
+                async move { check.wait_until_confirmed(Duration::from_millis(10)).await },
+            );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!confirmed.is_finished());
+
+        spawn_websocket_node(TcpListener::bind(address).await.unwrap(), chain_id::MAINNET).await;
+        confirmed.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn user_defined_profiles() {
+        let table: toml::Table = r#"
+            [networks.devnet]
+            chain_id = "SN_DEVNET"
+            staking_contract_address = "0x1"
+            attestation_contract_address = "0x2"
+        "#
+        .parse()
+        .unwrap();
+        let profiles = parse_profiles(table.get("networks")).unwrap();
+        assert_eq!(profiles[0].name, "devnet");
+
+        let devnet = parse_chain_id("SN_DEVNET").unwrap();
+        let addresses = ContractAddresses::new(devnet, &profiles, None, None, Some(felt!("0x3")));
+        assert_eq!(addresses.staking().unwrap(), felt!("0x1"));
+        assert_eq!(addresses.attestation().unwrap(), felt!("0x2"));
+        assert_eq!(addresses.strk().unwrap(), felt!("0x3"));
+
+        let addresses = ContractAddresses::new(devnet, &profiles, None, None, None);
+        assert_eq!(
+            addresses.strk().unwrap_err().to_string(),
+            "STRK contract address is required for chain ID SN_DEVNET, please specify it \
+             explicitly or in a network profile"
+        );
+
+        // Explicit addresses take precedence over the profile.
+        let addresses =
+            ContractAddresses::new(chain_id::MAINNET, &profiles, Some(felt!("0x4")), None, None);
+        assert_eq!(addresses.staking().unwrap(), felt!("0x4"));
+        assert_eq!(
+            addresses.attestation().unwrap(),
+            MAINNET_ATTESTATION_CONTRACT_ADDRESS
+        );
+    }
+
+    #[test]
+    fn expected_network() {
+        let profiles = [NetworkProfile {
+            name: "devnet".to_owned(),
+            chain_id: parse_chain_id("SN_DEVNET").unwrap(),
+            staking_contract_address: None,
+            attestation_contract_address: None,
+            strk_contract_address: None,
+        }];
+
+        let mainnet = ExpectedNetwork::resolve("mainnet", &profiles).unwrap();
+        assert!(mainnet.check(chain_id::MAINNET).is_ok());
+        assert_eq!(
+            mainnet.check(chain_id::SEPOLIA).unwrap_err().to_string(),
+            "The node is on chain SN_SEPOLIA, but the expected network mainnet is chain SN_MAIN"
+        );
+
+        let devnet = ExpectedNetwork::resolve("devnet", &profiles).unwrap();
+        assert_eq!(devnet.chain_id, profiles[0].chain_id);
+        let custom = ExpectedNetwork::resolve("0x1234", &profiles).unwrap();
+        assert_eq!(custom.chain_id, felt!("0x1234"));
+        assert_eq!(
+            ExpectedNetwork::resolve("SN_MAIN", &profiles)
+                .unwrap()
+                .chain_id,
+            chain_id::MAINNET
+        );
+    }
+}
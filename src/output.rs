@@ -0,0 +1,128 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+/// Output format of the subcommands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading in a terminal.
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// A row of subcommand output.
+pub trait Row: Serialize {
+    const HEADER: &'static [&'static str];
+
+    /// The values of the columns in `HEADER`.
+    fn columns(&self) -> Vec<String>;
+}
+
+pub fn write<R: Row>(
+    writer: &mut impl Write,
+    format: OutputFormat,
+    rows: &[R],
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => write_table(writer, rows)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, rows)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Csv => {
+            writeln!(writer, "{}", R::HEADER.join(","))?;
+            for row in rows {
+                let columns: Vec<_> = row.columns().iter().map(|c| csv_field(c)).collect();
+                writeln!(writer, "{}", columns.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_table<R: Row>(writer: &mut impl Write, rows: &[R]) -> std::io::Result<()> {
+    let rows: Vec<_> = rows.iter().map(Row::columns).collect();
+    let widths: Vec<_> = R::HEADER
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain([header.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let header: Vec<_> = R::HEADER.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{column:width$}"))
+            .collect();
+        writeln!(writer, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct TestRow {
+        name: &'static str,
+        value: u64,
+    }
+
+    impl Row for TestRow {
+        const HEADER: &'static [&'static str] = &["name", "value"];
+
+        fn columns(&self) -> Vec<String> {
+            vec![self.name.to_owned(), self.value.to_string()]
+        }
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let rows = [
+            TestRow {
+                name: "a, \"quoted\"",
+                value: 1,
+            },
+            TestRow {
+                name: "b",
+                value: 1000,
+            },
+        ];
+        let mut output = Vec::new();
+        write(&mut output, format, &rows).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            render(OutputFormat::Table),
+            "name         value\na, \"quoted\"  1\nb            1000\n"
+        );
+        assert_eq!(
+            render(OutputFormat::Csv),
+            "name,value\n\"a, \"\"quoted\"\"\",1\nb,1000\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&render(OutputFormat::Json)).unwrap(),
+            serde_json::json!([{"name": "a, \"quoted\"", "value": 1}, {"name": "b", "value": 1000}])
+        );
+    }
+}
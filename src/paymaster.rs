@@ -0,0 +1,652 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use starknet_rust::core::types::{Call, Felt, TypedData};
+use starknet_rust::core::utils::get_selector_from_name;
+use url::Url;
+
+use crate::secret::Secret;
+
+/// How far in the future a sponsored outside execution may stay valid. The
+/// signature could be relayed at any time until then.
+const MAX_EXECUTION_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaymasterError {
+    /// A transport error encountered while talking to the paymaster.
+    #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+    /// The paymaster returned a JSON-RPC error, for example because it refuses
+    /// to sponsor the transaction.
+    #[error("Paymaster returned error: {message} ({code})")]
+    Rpc { code: i64, message: String },
+    /// The paymaster returned something we are not willing to sign.
+    #[error("Invalid paymaster response: {0}")]
+    InvalidResponse(String),
+}
+
+impl PaymasterError {
+    /// Whether the paymaster has definitely not accepted the request, either
+    /// with a JSON-RPC error or with an HTTP client error status.
+    ///
+    /// After any other transport error or an unexpected response to
+    /// `paymaster_executeTransaction`, the paymaster may still relay the signed
+    /// outside execution.
+    pub fn is_refusal(&self) -> bool {
+        match self {
+            PaymasterError::Rpc { .. } => true,
+            PaymasterError::Transport(error) => error
+                .status()
+                .is_some_and(|status| status.is_client_error()),
+            PaymasterError::InvalidResponse(_) => false,
+        }
+    }
+}
+
+/// Client of a paymaster implementing the SNIP-29 API.
+///
+/// Attestations are sent as SNIP-9 (version 2) outside executions, with the
+/// fees paid by the paymaster.
+#[derive(Debug)]
+pub struct Paymaster {
+    url: Url,
+    client: reqwest::Client,
+}
+
+/// An outside execution built by the paymaster, waiting to be signed.
+#[derive(Debug)]
+pub struct SponsoredTransaction {
+    /// The typed data as returned by the paymaster. It is sent back unchanged.
+    raw_typed_data: serde_json::Value,
+    parameters: serde_json::Value,
+    pub typed_data: TypedData,
+    pub message_hash: Felt,
+    /// The time (in seconds since the Unix epoch) until which the outside
+    /// execution can be relayed.
+    pub execute_before: u64,
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a, T> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct BuildTransactionResult {
+    typed_data: serde_json::Value,
+    parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ExecuteTransactionResult {
+    transaction_hash: Felt,
+}
+
+impl Paymaster {
+    pub fn new(url: Url, api_key: Option<&Secret>) -> anyhow::Result<Self> {
+        Self::with_timeout(url, api_key, Duration::from_secs(30))
+    }
+
+    pub fn with_timeout(
+        url: Url,
+        api_key: Option<&Secret>,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(api_key) = api_key {
+            let mut value = reqwest::header::HeaderValue::from_str(api_key.expose())
+                .map_err(|_| anyhow::anyhow!("Paymaster API key is not a valid header value"))?;
+            value.set_sensitive(true);
+            headers.insert("x-paymaster-api-key", value);
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(timeout)
+            .build()?;
+
+        Ok(Self { url, client })
+    }
+
+    /// Asks the paymaster to build a sponsored outside execution of a single
+    /// call of `entry_point` on `to`.
+    ///
+    /// The typed data returned is checked to contain exactly that call, to be
+    /// valid on `chain_id` only and for a bounded time.
+    pub async fn build_transaction(
+        &self,
+        chain_id: Felt,
+        user_address: Felt,
+        to: Felt,
+        entry_point: &str,
+        calldata: Vec<Felt>,
+    ) -> Result<SponsoredTransaction, PaymasterError> {
+        let call = Call {
+            to,
+            selector: get_selector_from_name(entry_point)
+                .map_err(|error| PaymasterError::InvalidResponse(error.to_string()))?,
+            calldata,
+        };
+        let result: BuildTransactionResult = self
+            .request(
+                "paymaster_buildTransaction",
+                serde_json::json!({
+                    "transaction": {
+                        "type": "invoke",
+                        "invoke": {
+                            "user_address": user_address,
+                            "calls": [call],
+                        },
+                    },
+                    "parameters": {
+                        "version": "0x1",
+                        "fee_mode": { "mode": "sponsored" },
+                    },
+                }),
+            )
+            .await?;
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let typed_data = checked_typed_data(&result.typed_data, &call, entry_point, chain_id, now)?;
+        let (_, execute_before) = execution_window(&result.typed_data).ok_or_else(|| {
+            PaymasterError::InvalidResponse(
+                "Outside execution has no valid execution window".to_owned(),
+            )
+        })?;
+        let message_hash = typed_data
+            .message_hash(user_address)
+            .map_err(|error| PaymasterError::InvalidResponse(error.to_string()))?;
+
+        Ok(SponsoredTransaction {
+            raw_typed_data: result.typed_data,
+            parameters: result.parameters,
+            typed_data,
+            message_hash,
+            execute_before,
+        })
+    }
+
+    /// Submits the signed outside execution, returning the hash of the
+    /// transaction sent by the paymaster.
+    pub async fn execute_transaction(
+        &self,
+        user_address: Felt,
+        transaction: &SponsoredTransaction,
+        signature: Vec<Felt>,
+    ) -> Result<Felt, PaymasterError> {
+        let result: ExecuteTransactionResult = self
+            .request(
+                "paymaster_executeTransaction",
+                serde_json::json!({
+                    "transaction": {
+                        "type": "invoke",
+                        "invoke": {
+                            "user_address": user_address,
+                            "typed_data": transaction.raw_typed_data,
+                            "signature": signature,
+                        },
+                    },
+                    "parameters": transaction.parameters,
+                }),
+            )
+            .await?;
+
+        Ok(result.transaction_hash)
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, PaymasterError> {
+        let response: JsonRpcResponse<T> = self
+            .client
+            .post(self.url.clone())
+            .json(&JsonRpcRequest {
+                jsonrpc: "2.0",
+                id: 1,
+                method,
+                params,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(PaymasterError::Rpc {
+                code: error.code,
+                message: error.message,
+            }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(PaymasterError::InvalidResponse(
+                "Neither result nor error in response".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Checks that `typed_data` is a SNIP-9 version 2 outside execution of `call`
+/// only, on `chain_id`, that can only be executed between `now` (the time
+/// since the Unix epoch) and [`MAX_EXECUTION_WINDOW`] later, and parses it.
+///
+/// SNIP-12 allows `selector` values to be given either as an entry point name
+/// or as the selector itself, but the latter is not supported by the encoder:
+/// the selector is replaced by `entry_point` before parsing. This does not
+/// change the message hash.
+fn checked_typed_data(
+    typed_data: &serde_json::Value,
+    call: &Call,
+    entry_point: &str,
+    chain_id: Felt,
+    now: Duration,
+) -> Result<TypedData, PaymasterError> {
+    let invalid = |message: &str| PaymasterError::InvalidResponse(message.to_owned());
+
+    if typed_data["primaryType"] != "OutsideExecution" {
+        return Err(invalid("Typed data is not an outside execution"));
+    }
+    if typed_data["types"]["Call"]
+        .as_array()
+        .and_then(|fields| fields.iter().find(|field| field["name"] == "Selector"))
+        .is_none_or(|field| field["type"] != "selector")
+    {
+        return Err(invalid("Only version 2 outside executions are supported"));
+    }
+
+    // A signature for another chain could be replayed there.
+    if typed_data["domain"]["chainId"]
+        .as_str()
+        .and_then(|value| crate::network::parse_chain_id(value).ok())
+        != Some(chain_id)
+    {
+        return Err(invalid(
+            "Outside execution is not for the chain of the node",
+        ));
+    }
+    let Some((execute_after, execute_before)) = execution_window(typed_data) else {
+        return Err(invalid("Outside execution has no valid execution window"));
+    };
+    if execute_before <= now.as_secs()
+        || execute_before > (now + MAX_EXECUTION_WINDOW).as_secs()
+        || execute_after >= execute_before
+    {
+        return Err(invalid(
+            "Outside execution window is in the past or too far in the future",
+        ));
+    }
+
+    let mut typed_data = typed_data.clone();
+    let calls = typed_data["message"]["Calls"]
+        .as_array_mut()
+        .ok_or_else(|| invalid("Outside execution has no calls"))?;
+    let [sponsored_call] = &mut calls[..] else {
+        return Err(invalid("Outside execution must contain exactly one call"));
+    };
+
+    let selector_matches = match &sponsored_call["Selector"] {
+        serde_json::Value::String(selector) => {
+            selector == entry_point || parse_felt(selector) == Some(call.selector)
+        }
+        _ => false,
+    };
+    let calldata = sponsored_call["Calldata"].as_array().map(|calldata| {
+        calldata
+            .iter()
+            .map(|value| value.as_str().and_then(parse_felt))
+            .collect::<Option<Vec<_>>>()
+    });
+    if sponsored_call["To"].as_str().and_then(parse_felt) != Some(call.to)
+        || !selector_matches
+        || calldata != Some(Some(call.calldata.clone()))
+    {
+        return Err(invalid("Outside execution does not match the attestation"));
+    }
+    sponsored_call["Selector"] = entry_point.into();
+
+    serde_json::from_value(typed_data).map_err(|error| invalid(&error.to_string()))
+}
+
+/// The "Execute After" and "Execute Before" timestamps of an outside
+/// execution.
+fn execution_window(typed_data: &serde_json::Value) -> Option<(u64, u64)> {
+    let timestamp = |name: &str| {
+        typed_data["message"][name]
+            .as_str()
+            .and_then(parse_felt)
+            .and_then(|value| u64::try_from(value).ok())
+    };
+    Some((timestamp("Execute After")?, timestamp("Execute Before")?))
+}
+
+fn parse_felt(value: &str) -> Option<Felt> {
+    if value.starts_with("0x") {
+        Felt::from_hex(value).ok()
+    } else {
+        Felt::from_dec_str(value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use starknet_rust::{core::chain_id, macros::felt};
+
+    use super::*;
+
+    const USER_ADDRESS: Felt = felt!("0xfeedbeef");
+    const ATTESTATION_CONTRACT_ADDRESS: Felt = felt!("0xabcdef");
+    const BLOCK_HASH: Felt = felt!("0x123456789abcdef");
+    const TRANSACTION_HASH: Felt = felt!("0x1234");
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn typed_data(calls: serde_json::Value) -> serde_json::Value {
+        typed_data_with(calls, "SN_SEPOLIA", now() - 1, now() + 600)
+    }
+
+    fn typed_data_with(
+        calls: serde_json::Value,
+        chain_id: &str,
+        execute_after: u64,
+        execute_before: u64,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "types": {
+                "StarknetDomain": [
+                    { "name": "name", "type": "shortstring" },
+                    { "name": "version", "type": "shortstring" },
+                    { "name": "chainId", "type": "shortstring" },
+                    { "name": "revision", "type": "shortstring" },
+                ],
+                "OutsideExecution": [
+                    { "name": "Caller", "type": "ContractAddress" },
+                    { "name": "Nonce", "type": "felt" },
+                    { "name": "Execute After", "type": "u128" },
+                    { "name": "Execute Before", "type": "u128" },
+                    { "name": "Calls", "type": "Call*" },
+                ],
+                "Call": [
+                    { "name": "To", "type": "ContractAddress" },
+                    { "name": "Selector", "type": "selector" },
+                    { "name": "Calldata", "type": "felt*" },
+                ],
+            },
+            "primaryType": "OutsideExecution",
+            "domain": {
+                "name": "Account.execute_from_outside",
+                "version": "2",
+                "chainId": chain_id,
+                "revision": "1",
+            },
+            "message": {
+                "Caller": "0x414e595f43414c4c4552",
+                "Nonce": "0x1",
+                "Execute After": format!("{execute_after:#x}"),
+                "Execute Before": format!("{execute_before:#x}"),
+                "Calls": calls,
+            },
+        })
+    }
+
+    fn attest_call(selector: &str) -> serde_json::Value {
+        serde_json::json!({
+            "To": format!("{ATTESTATION_CONTRACT_ADDRESS:#x}"),
+            "Selector": selector,
+            "Calldata": [format!("{BLOCK_HASH:#x}")],
+        })
+    }
+
+    /// Spawns a paymaster returning `typed_data` from `paymaster_buildTransaction`.
+    ///
+    /// Returns the URL and the last request received.
+    async fn spawn_paymaster(
+        typed_data: serde_json::Value,
+    ) -> (Url, Arc<Mutex<Option<serde_json::Value>>>) {
+        spawn_paymaster_with(typed_data, OnExecute::Succeed).await
+    }
+
+    /// How the paymaster answers `paymaster_executeTransaction`.
+    #[derive(Clone, Copy)]
+    enum OnExecute {
+        Succeed,
+        Hang,
+        Fail(axum::http::StatusCode),
+    }
+
+    /// Like [`spawn_paymaster`], but answering `paymaster_executeTransaction`
+    /// as set by `on_execute`.
+    async fn spawn_paymaster_with(
+        typed_data: serde_json::Value,
+        on_execute: OnExecute,
+    ) -> (Url, Arc<Mutex<Option<serde_json::Value>>>) {
+        let last_request = Arc::new(Mutex::new(None));
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post({
+                let last_request = last_request.clone();
+                move |axum::Json(request): axum::Json<serde_json::Value>| async move {
+                    use axum::response::IntoResponse;
+
+                    let result = match request["method"].as_str() {
+                        Some("paymaster_buildTransaction") => serde_json::json!({
+                            "type": "invoke",
+                            "typed_data": typed_data,
+                            "parameters": request["params"]["parameters"],
+                            "fee": {},
+                        }),
+                        _ => {
+                            *last_request.lock().unwrap() = Some(request);
+                            match on_execute {
+                                OnExecute::Succeed => {}
+                                OnExecute::Hang => std::future::pending::<()>().await,
+                                OnExecute::Fail(status) => return status.into_response(),
+                            }
+                            serde_json::json!({
+                                "transaction_hash": TRANSACTION_HASH,
+                                "tracking_id": "0x1",
+                            })
+                        }
+                    };
+                    axum::Json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+                        .into_response()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (url, last_request)
+    }
+
+    #[tokio::test]
+    async fn sponsored_transaction() {
+        let selector = get_selector_from_name("attest").unwrap();
+        let (url, last_request) = spawn_paymaster(typed_data(serde_json::json!([attest_call(
+            &format!("{selector:#x}")
+        )])))
+        .await;
+        let paymaster = Paymaster::new(url, None).unwrap();
+
+        let transaction = paymaster
+            .build_transaction(
+                chain_id::SEPOLIA,
+                USER_ADDRESS,
+                ATTESTATION_CONTRACT_ADDRESS,
+                "attest",
+                vec![BLOCK_HASH],
+            )
+            .await
+            .unwrap();
+
+        // Giving the selector by name results in the same message hash.
+        let by_name: TypedData =
+            serde_json::from_value(typed_data(serde_json::json!([attest_call("attest")]))).unwrap();
+        assert_eq!(
+            transaction.message_hash,
+            by_name.message_hash(USER_ADDRESS).unwrap()
+        );
+
+        let transaction_hash = paymaster
+            .execute_transaction(USER_ADDRESS, &transaction, vec![Felt::ONE, Felt::TWO])
+            .await
+            .unwrap();
+        assert_eq!(transaction_hash, TRANSACTION_HASH);
+
+        // The typed data is sent back as returned by the paymaster.
+        let request = last_request.lock().unwrap().take().unwrap();
+        assert_eq!(
+            request["params"]["transaction"]["invoke"]["typed_data"]["message"]["Calls"][0]["Selector"],
+            format!("{selector:#x}")
+        );
+        assert_eq!(
+            request["params"]["transaction"]["invoke"]["signature"],
+            serde_json::json!(["0x1", "0x2"])
+        );
+    }
+
+    #[tokio::test]
+    async fn unexpected_calls_are_refused() {
+        let transfer = serde_json::json!({
+            "To": "0x4718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d",
+            "Selector": "transfer",
+            "Calldata": ["0x1", "0x1000", "0x0"],
+        });
+        let (url, _) = spawn_paymaster(typed_data(serde_json::json!([
+            attest_call("attest"),
+            transfer
+        ])))
+        .await;
+        let paymaster = Paymaster::new(url, None).unwrap();
+
+        assert_matches::assert_matches!(
+            paymaster
+                .build_transaction(
+                    chain_id::SEPOLIA,
+                    USER_ADDRESS,
+                    ATTESTATION_CONTRACT_ADDRESS,
+                    "attest",
+                    vec![BLOCK_HASH],
+                )
+                .await,
+            Err(PaymasterError::InvalidResponse(_))
+        );
+    }
+
+    #[test]
+    fn other_chains_and_unbounded_windows_are_refused() {
+        let call = Call {
+            to: ATTESTATION_CONTRACT_ADDRESS,
+            selector: get_selector_from_name("attest").unwrap(),
+            calldata: vec![BLOCK_HASH],
+        };
+        let now = now();
+        let check = |typed_data| {
+            checked_typed_data(
+                &typed_data,
+                &call,
+                "attest",
+                chain_id::SEPOLIA,
+                Duration::from_secs(now),
+            )
+        };
+        let calls = || serde_json::json!([attest_call("attest")]);
+
+        assert!(check(typed_data_with(calls(), "SN_SEPOLIA", now - 1, now + 600)).is_ok());
+        for typed_data in [
+            typed_data_with(calls(), "SN_MAIN", now - 1, now + 600),
+            // Expired.
+            typed_data_with(calls(), "SN_SEPOLIA", now - 600, now - 1),
+            // Valid for too long.
+            typed_data_with(calls(), "SN_SEPOLIA", now - 1, now + 7 * 24 * 60 * 60),
+        ] {
+            assert_matches::assert_matches!(
+                check(typed_data),
+                Err(PaymasterError::InvalidResponse(_))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_timeout_is_not_a_refusal() {
+        let (url, last_request) = spawn_paymaster_with(
+            typed_data(serde_json::json!([attest_call("attest")])),
+            OnExecute::Hang,
+        )
+        .await;
+        let paymaster = Paymaster::with_timeout(url, None, Duration::from_millis(200)).unwrap();
+        let transaction = paymaster
+            .build_transaction(
+                chain_id::SEPOLIA,
+                USER_ADDRESS,
+                ATTESTATION_CONTRACT_ADDRESS,
+                "attest",
+                vec![BLOCK_HASH],
+            )
+            .await
+            .unwrap();
+
+        let error = paymaster
+            .execute_transaction(USER_ADDRESS, &transaction, vec![Felt::ONE, Felt::TWO])
+            .await
+            .unwrap_err();
+
+        // The paymaster has received the signed outside execution and may
+        // still relay it.
+        assert!(last_request.lock().unwrap().is_some());
+        assert_matches::assert_matches!(error, PaymasterError::Transport(_));
+        assert!(!error.is_refusal());
+    }
+
+    #[tokio::test]
+    async fn execute_client_error_is_a_refusal() {
+        let (url, _) = spawn_paymaster_with(
+            typed_data(serde_json::json!([attest_call("attest")])),
+            OnExecute::Fail(axum::http::StatusCode::UNAUTHORIZED),
+        )
+        .await;
+        let paymaster = Paymaster::new(url, None).unwrap();
+        let transaction = paymaster
+            .build_transaction(
+                chain_id::SEPOLIA,
+                USER_ADDRESS,
+                ATTESTATION_CONTRACT_ADDRESS,
+                "attest",
+                vec![BLOCK_HASH],
+            )
+            .await
+            .unwrap();
+
+        let error = paymaster
+            .execute_transaction(USER_ADDRESS, &transaction, vec![Felt::ONE, Felt::TWO])
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(error, PaymasterError::Transport(_));
+        assert!(error.is_refusal());
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Context;
+use starknet_rust::providers::{JsonRpcClient, Provider, jsonrpc::HttpTransport};
+
+/// The version of the JSON-RPC API specification the request and response
+/// types of `starknet-rust` follow.
+const JSON_RPC_API_VERSION_REQUIRED: &str = ">=0.10.0,<0.11.0";
+
+/// Checks that the node endpoint serves the version of the JSON-RPC API
+/// required by this tool, returning that version.
+pub async fn check(client: &JsonRpcClient<HttpTransport>) -> anyhow::Result<semver::Version> {
+    let spec_version: semver::Version = client
+        .spec_version()
+        .await
+        .context("Getting spec version of node endpoint")?
+        .parse()
+        .context("Parsing JSON-RPC API specification version")?;
+    if !is_supported(&spec_version) {
+        anyhow::bail!(
+            "Inappropriate JSON-RPC API version {spec_version}. This tool requires 0.10.0, usually served on an URL ending in `v0_10`"
+        );
+    }
+    Ok(spec_version)
+}
+
+fn is_supported(spec_version: &semver::Version) -> bool {
+    semver::VersionReq::parse(JSON_RPC_API_VERSION_REQUIRED)
+        .expect("JSON-RPC version requirements should be OK")
+        .matches(spec_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_versions() {
+        assert!(is_supported(&"0.10.0".parse().unwrap()));
+        assert!(is_supported(&"0.10.1".parse().unwrap()));
+        assert!(!is_supported(&"0.9.0".parse().unwrap()));
+        assert!(!is_supported(&"0.11.0".parse().unwrap()));
+    }
+}
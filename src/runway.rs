@@ -0,0 +1,171 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of recent epochs the forecast is based on.
+const SAMPLE_SIZE: usize = 10;
+
+/// Forecast of how long the operational account balance lasts, based on the
+/// fees of recent attestations and the durations of recent epochs.
+#[derive(Debug, Default)]
+pub struct Runway {
+    balance: Option<u128>,
+    fees: VecDeque<u128>,
+    epoch_durations: VecDeque<Duration>,
+    epoch_start: Option<(u64, Instant)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Forecast {
+    pub epochs_remaining: f64,
+    /// Unknown until a full epoch has been observed.
+    pub runway: Option<Duration>,
+}
+
+impl Runway {
+    pub fn record_balance(&mut self, balance: u128) {
+        self.balance = Some(balance);
+    }
+
+    /// Records the actual fee of a confirmed or reverted attestation.
+    pub fn record_fee(&mut self, fee: u128) {
+        push_sample(&mut self.fees, fee);
+    }
+
+    /// Records the start of epoch `epoch_id`, observed at `now`.
+    pub fn record_epoch_start(&mut self, epoch_id: u64, now: Instant) {
+        // Only the duration of consecutive epochs is known.
+        if let Some((previous_epoch_id, previous_start)) = self.epoch_start
+            && previous_epoch_id + 1 == epoch_id
+        {
+            push_sample(
+                &mut self.epoch_durations,
+                now.duration_since(previous_start),
+            );
+        }
+        self.epoch_start = Some((epoch_id, now));
+    }
+
+    pub fn forecast(&self) -> Option<Forecast> {
+        let balance = self.balance?;
+        let average_fee = average(self.fees.iter().map(|fee| *fee as f64))?;
+        if average_fee == 0.0 {
+            return None;
+        }
+
+        let epochs_remaining = balance as f64 / average_fee;
+        let runway = average(
+            self.epoch_durations
+                .iter()
+                .map(|duration| duration.as_secs_f64()),
+        )
+        // Too long to represent, for example with a tiny fee on a devnet.
+        .and_then(|epoch_duration| {
+            Duration::try_from_secs_f64(epochs_remaining * epoch_duration).ok()
+        });
+
+        Some(Forecast {
+            epochs_remaining,
+            runway,
+        })
+    }
+
+    /// Updates the runway gauges.
+    pub fn export(&self) {
+        let Some(forecast) = self.forecast() else {
+            return;
+        };
+
+        metrics::gauge!("validator_attestation_epochs_remaining").set(forecast.epochs_remaining);
+        if let Some(runway) = forecast.runway {
+            metrics::gauge!("validator_attestation_estimated_runway_seconds")
+                .set(runway.as_secs_f64());
+        }
+        tracing::debug!(?forecast, "Updated balance runway forecast");
+    }
+}
+
+fn push_sample<T>(samples: &mut VecDeque<T>, sample: T) {
+    if samples.len() == SAMPLE_SIZE {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+fn average(samples: impl ExactSizeIterator<Item = f64>) -> Option<f64> {
+    let count = samples.len();
+    if count == 0 {
+        return None;
+    }
+    Some(samples.sum::<f64>() / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRK: u128 = 1_000_000_000_000_000_000;
+
+    #[test]
+    fn forecast() {
+        let mut runway = Runway::default();
+        let start = Instant::now();
+        runway.record_balance(10 * STRK);
+        assert_eq!(runway.forecast(), None);
+
+        runway.record_fee(STRK / 2);
+        runway.record_fee(STRK * 3 / 2);
+        assert_eq!(
+            runway.forecast(),
+            Some(Forecast {
+                epochs_remaining: 10.0,
+                runway: None
+            })
+        );
+
+        // Durations of consecutive epochs are averaged.
+        runway.record_epoch_start(5, start);
+        runway.record_epoch_start(6, start + Duration::from_secs(100));
+        runway.record_epoch_start(7, start + Duration::from_secs(300));
+        assert_eq!(
+            runway.forecast(),
+            Some(Forecast {
+                epochs_remaining: 10.0,
+                runway: Some(Duration::from_secs(1500))
+            })
+        );
+
+        // Skipped epochs are not used.
+        runway.record_epoch_start(9, start + Duration::from_secs(1000));
+        runway.record_epoch_start(10, start + Duration::from_secs(1150));
+        assert_eq!(
+            runway.forecast().unwrap().runway,
+            Some(Duration::from_secs(1500))
+        );
+    }
+
+    #[test]
+    fn only_recent_fees_are_used() {
+        let mut runway = Runway::default();
+        runway.record_balance(100 * STRK);
+        runway.record_fee(100 * STRK);
+        for _ in 0..SAMPLE_SIZE {
+            runway.record_fee(STRK);
+        }
+
+        assert_eq!(runway.forecast().unwrap().epochs_remaining, 100.0);
+    }
+
+    #[test]
+    fn unrepresentable_runway() {
+        let mut runway = Runway::default();
+        let start = Instant::now();
+        runway.record_balance(u128::MAX);
+        runway.record_fee(1);
+        runway.record_epoch_start(5, start);
+        runway.record_epoch_start(6, start + Duration::from_secs(100));
+
+        assert_eq!(runway.forecast().unwrap().runway, None);
+    }
+}
@@ -1,5 +1,7 @@
+use std::time::{Duration, Instant};
+
 use serde::{Deserialize, Serialize};
-use starknet_rust::core::types::{BroadcastedInvokeTransactionV3, Felt};
+use starknet_rust::core::types::{BroadcastedInvokeTransactionV3, Felt, TypedData};
 use starknet_rust::signers::{LocalWallet, Signer, SignerInteractivityContext};
 
 #[derive(Debug, thiserror::Error)]
@@ -7,9 +9,24 @@ pub enum SignError {
     /// An error encountered by the signer implementation.
     #[error(transparent)]
     Signing(starknet_rust::core::crypto::EcdsaSignError),
+    /// The signer returned a signature of unexpected shape.
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    /// Fewer signers than the multisig threshold returned a signature in time.
+    #[error("Collected {collected} signatures, but {threshold} are required")]
+    ThresholdNotMet { collected: usize, threshold: usize },
+    /// The operation is not supported by the signer.
+    #[error("Unsupported operation: {0}")]
+    Unsupported(&'static str),
     /// A transport error encountered during remote signing.
     #[error(transparent)]
     Transport(reqwest::Error),
+    /// The remote signer refused to sign the transaction.
+    #[error("Remote signer rejected the request: {message} ({code})")]
+    Rejected { code: String, message: String },
+    /// No remote signer endpoint returned a signature before the deadline.
+    #[error("No remote signer returned a signature within {0:?}")]
+    DeadlineExceeded(Duration),
 }
 
 impl From<starknet_rust::signers::local_wallet::SignError> for SignError {
@@ -22,98 +39,715 @@ impl From<starknet_rust::signers::local_wallet::SignError> for SignError {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum AttestationSigner {
-    Local(LocalWallet),
-    Remote(RemoteSigner),
+/// A backend signing attestation transactions.
+///
+/// [`LocalWallet`] and [`RemoteSigner`] are the built-in implementations.
+#[async_trait::async_trait]
+pub trait AttestationSigner: std::fmt::Debug + Send + Sync {
+    /// Signs the transaction in `request`, returning the signature expected by
+    /// the account contract.
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError>;
+
+    /// The public key of the signer.
+    async fn public_key(&self) -> Result<Felt, SignError>;
+
+    /// Whether signing involves anything besides local computation.
+    ///
+    /// See [`Signer::is_interactive`].
+    fn is_interactive(&self, context: SignerInteractivityContext<'_>) -> bool;
+
+    /// Signs the transaction in `request`, laying out the signature as
+    /// expected by accounts using `format`.
+    async fn sign_with_format(
+        &self,
+        request: &SigningRequest,
+        format: SignatureFormat,
+    ) -> Result<Vec<Felt>, SignError> {
+        let signature = self.sign(request).await?;
+        format.apply(self, signature).await
+    }
+
+    /// Signs the SNIP-12 typed data in `request`, returning the signature
+    /// expected by the account contract.
+    ///
+    /// Used for outside executions sponsored by a paymaster. Signers not
+    /// supporting this fall back to self-paid transactions.
+    async fn sign_typed_data(
+        &self,
+        _request: &TypedDataSigningRequest,
+    ) -> Result<Vec<Felt>, SignError> {
+        Err(SignError::Unsupported("signing typed data"))
+    }
+}
+
+/// The signature layout expected by the operational account contract.
+///
+/// All supported accounts sign the standard v3 transaction hash, they only
+/// differ in how the signature is passed to `__validate__`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum SignatureFormat {
+    /// `[r, s]`, expected by OpenZeppelin and Ready accounts, and by Braavos
+    /// accounts with a Stark signer.
+    #[default]
+    Standard,
+    /// `[1, 0, public_key, r, s]`: the `Array<SignerSignature>` layout of
+    /// Argent accounts since v0.4.
+    Argent,
 }
 
-impl AttestationSigner {
-    pub fn new_local(wallet: LocalWallet) -> Self {
-        Self::Local(wallet)
+impl SignatureFormat {
+    /// Lays out the `[r, s]` signature returned by `signer` in this format.
+    pub async fn apply<S: AttestationSigner + ?Sized>(
+        self,
+        signer: &S,
+        signature: Vec<Felt>,
+    ) -> Result<Vec<Felt>, SignError> {
+        match self {
+            SignatureFormat::Standard => Ok(signature),
+            SignatureFormat::Argent => {
+                let [r, s] = signature[..] else {
+                    return Err(SignError::InvalidSignature(format!(
+                        "Expected an [r, s] signature, got {} elements",
+                        signature.len()
+                    )));
+                };
+                // A single `SignerSignature::Starknet`.
+                Ok(vec![
+                    Felt::ONE,
+                    Felt::ZERO,
+                    signer.public_key().await?,
+                    r,
+                    s,
+                ])
+            }
+        }
     }
+}
 
-    pub fn new_remote(url: url::Url) -> anyhow::Result<Self> {
-        Ok(Self::Remote(RemoteSigner::new(url)?))
+#[async_trait::async_trait]
+impl<S: AttestationSigner + ?Sized> AttestationSigner for Box<S> {
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+        (**self).sign(request).await
     }
 
-    pub async fn sign(
+    async fn sign_typed_data(
         &self,
-        hash: &Felt,
-        transaction: BroadcastedInvokeTransactionV3,
-        chain_id: Felt,
+        request: &TypedDataSigningRequest,
     ) -> Result<Vec<Felt>, SignError> {
-        let signature = match self {
-            Self::Local(wallet) => {
-                let signature = wallet.sign_hash(hash).await?;
-                vec![signature.r, signature.s]
-            }
-            Self::Remote(signer) => signer.sign(transaction, chain_id).await?,
-        };
-        Ok(signature)
+        (**self).sign_typed_data(request).await
     }
 
-    pub fn is_signer_interactive(&self, context: SignerInteractivityContext<'_>) -> bool {
-        match self {
-            Self::Local(_) => false,
-            Self::Remote(signer) => signer.is_interactive(context),
+    async fn public_key(&self) -> Result<Felt, SignError> {
+        (**self).public_key().await
+    }
+
+    fn is_interactive(&self, context: SignerInteractivityContext<'_>) -> bool {
+        (**self).is_interactive(context)
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: AttestationSigner + ?Sized> AttestationSigner for std::sync::Arc<S> {
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+        (**self).sign(request).await
+    }
+
+    async fn sign_typed_data(
+        &self,
+        request: &TypedDataSigningRequest,
+    ) -> Result<Vec<Felt>, SignError> {
+        (**self).sign_typed_data(request).await
+    }
+
+    async fn public_key(&self) -> Result<Felt, SignError> {
+        (**self).public_key().await
+    }
+
+    fn is_interactive(&self, context: SignerInteractivityContext<'_>) -> bool {
+        (**self).is_interactive(context)
+    }
+}
+
+#[async_trait::async_trait]
+impl AttestationSigner for LocalWallet {
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+        let signature = self.sign_hash(&request.transaction_hash).await?;
+        Ok(vec![signature.r, signature.s])
+    }
+
+    async fn sign_typed_data(
+        &self,
+        request: &TypedDataSigningRequest,
+    ) -> Result<Vec<Felt>, SignError> {
+        let signature = self.sign_hash(&request.message_hash).await?;
+        Ok(vec![signature.r, signature.s])
+    }
+
+    async fn public_key(&self) -> Result<Felt, SignError> {
+        let public_key = self
+            .get_public_key()
+            .await
+            .unwrap_or_else(|never| match never {});
+        Ok(public_key.scalar())
+    }
+
+    fn is_interactive(&self, _context: SignerInteractivityContext<'_>) -> bool {
+        false
+    }
+}
+
+/// An attestation transaction to be signed.
+#[derive(Clone, Debug)]
+pub struct SigningRequest {
+    pub transaction_hash: Felt,
+    pub transaction: BroadcastedInvokeTransactionV3,
+    pub chain_id: Felt,
+    pub context: AttestationContext,
+}
+
+/// SNIP-12 typed data to be signed on behalf of the operational account.
+#[derive(Clone, Debug)]
+pub struct TypedDataSigningRequest {
+    pub message_hash: Felt,
+    pub typed_data: TypedData,
+    pub account_address: Felt,
+    pub context: AttestationContext,
+}
+
+/// Details of the attestation being signed.
+///
+/// Sent to remote signers using [`RemoteSignerProtocol::V2`] so that they can
+/// enforce a signing policy without decoding the transaction.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttestationContext {
+    pub staker_address: Felt,
+    pub epoch_id: u64,
+    pub block_number: u64,
+    pub block_hash: Felt,
+    pub attestation_contract_address: Felt,
+}
+
+/// Version of the request body sent to the `/sign` endpoint of remote signers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum RemoteSignerProtocol {
+    /// The transaction and the chain ID only.
+    #[default]
+    V1,
+    /// V1 extended with the transaction hash and the attestation context.
+    V2,
+}
+
+/// Options for [`RemoteSigner`].
+#[derive(Clone, Debug)]
+pub struct RemoteSignerOptions {
+    pub protocol: RemoteSignerProtocol,
+    /// Timeout of a single request.
+    pub timeout: Duration,
+    /// Number of additional rounds over all endpoints after a failed one.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each subsequent retry.
+    pub retry_delay: Duration,
+    /// Overall time allowed for signing, over all endpoints and retries.
+    pub deadline: Duration,
+}
+
+impl Default for RemoteSignerOptions {
+    fn default() -> Self {
+        Self {
+            protocol: RemoteSignerProtocol::default(),
+            timeout: Duration::from_secs(60),
+            retries: 0,
+            retry_delay: Duration::from_millis(500),
+            deadline: Duration::from_secs(5),
         }
     }
 }
 
+/// A signer using one or more remote signer endpoints.
+///
+/// All endpoints are expected to front the same key. They are tried in order
+/// until one of them returns a signature or refuses to sign the transaction.
 #[derive(Debug, Clone)]
 pub struct RemoteSigner {
+    endpoints: Vec<Endpoint>,
+    options: RemoteSignerOptions,
+}
+
+#[derive(Debug, Clone)]
+struct Endpoint {
+    /// The configured URL without credentials, used in logs and metrics.
+    label: String,
     url: url::Url,
     client: reqwest::Client,
 }
 
-impl RemoteSigner {
-    /// Constructs [`RemoteSigner`] from a [`reqwest::Client`].
-    pub fn new(url: url::Url) -> anyhow::Result<Self> {
+impl Endpoint {
+    /// URLs of the form `unix:///path/to/socket` connect to a signer listening
+    /// on a Unix domain socket.
+    fn new(url: url::Url, timeout: Duration) -> anyhow::Result<Self> {
+        let label = crate::secret::redact_url(&url);
+
+        let builder = reqwest::Client::builder().timeout(timeout);
+        let (url, builder) = if url.scheme() == "unix" {
+            anyhow::ensure!(
+                url.host().is_none() && url.path() != "/",
+                "Unix socket URLs should be of the form unix:///path/to/socket"
+            );
+            let socket_path = std::path::PathBuf::from(url.path());
+            // Requests are sent over the socket, the host only ends up in the
+            // Host header.
+            let url = url::Url::parse("http://localhost/").expect("URL is valid");
+            (url, builder.unix_socket(socket_path))
+        } else {
+            (url, builder)
+        };
+
         Ok(Self {
+            label,
             url,
-            client: reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(60))
-                .build()?,
+            client: builder.build()?,
         })
     }
-}
 
-impl RemoteSigner {
     async fn sign(
         &self,
-        transaction: BroadcastedInvokeTransactionV3,
-        chain_id: Felt,
+        path: &str,
+        request: &(impl Serialize + Sync),
+    ) -> Result<Vec<Felt>, SignError> {
+        let started = Instant::now();
+        let result = self.send(path, request).await;
+        metrics::histogram!(
+            "validator_attestation_remote_signer_request_duration_seconds",
+            "endpoint" => self.label.clone()
+        )
+        .record(started.elapsed().as_secs_f64());
+        if let Err(SignError::Transport(_)) = &result {
+            metrics::counter!(
+                "validator_attestation_remote_signer_error_count",
+                "endpoint" => self.label.clone()
+            )
+            .increment(1);
+        }
+        result
+    }
+
+    async fn get_public_key(&self) -> Result<Felt, SignError> {
+        let public_key = self
+            .client
+            .get(self.url.join("/get_public_key").unwrap())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(transport_error)?
+            .json::<PublicKeyResponse>()
+            .await
+            .map_err(transport_error)?
+            .public_key;
+        Ok(public_key)
+    }
+
+    async fn send(
+        &self,
+        path: &str,
+        request: &(impl Serialize + Sync),
     ) -> Result<Vec<Felt>, SignError> {
-        let signature = self
+        let response = self
             .client
-            .post(self.url.join("/sign").unwrap())
-            .json(&SignRequest {
-                transaction,
-                chain_id,
-            })
+            .post(self.url.join(path).unwrap())
+            .json(request)
             .send()
             .await
-            .map_err(SignError::Transport)?
+            .map_err(transport_error)?;
+
+        if response.status().is_client_error() {
+            let status = response.status();
+            return match response.json::<SignErrorResponse>().await {
+                Ok(SignErrorResponse { error }) => Err(SignError::Rejected {
+                    code: error.code,
+                    message: error.message,
+                }),
+                Err(_) => Err(SignError::Rejected {
+                    code: status.as_u16().to_string(),
+                    message: status.to_string(),
+                }),
+            };
+        }
+
+        let signature = response
+            .error_for_status()
+            .map_err(transport_error)?
             .json::<SignHashResponse>()
             .await
-            .map_err(SignError::Transport)?
+            .map_err(transport_error)?
             .signature;
         Ok(signature)
     }
+}
+
+/// The URL is left out of the error, it may contain credentials.
+fn transport_error(error: reqwest::Error) -> SignError {
+    SignError::Transport(error.without_url())
+}
+
+impl RemoteSigner {
+    pub fn new(urls: Vec<url::Url>, options: RemoteSignerOptions) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !urls.is_empty(),
+            "At least one remote signer URL is required"
+        );
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint::new(url, options.timeout))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { endpoints, options })
+    }
+
+    /// Sends `request` to the endpoints in order until one of them returns a
+    /// signature, retrying with backoff if all of them fail, until the
+    /// deadline: signing blocks the handling of new blocks.
+    async fn sign_with_failover(
+        &self,
+        path: &str,
+        request: &(impl Serialize + Sync),
+    ) -> Result<Vec<Felt>, SignError> {
+        let deadline = tokio::time::Instant::now() + self.options.deadline;
+        tokio::time::timeout_at(deadline, self.sign_with_retries(path, request, deadline))
+            .await
+            .unwrap_or(Err(SignError::DeadlineExceeded(self.options.deadline)))
+    }
+
+    async fn sign_with_retries(
+        &self,
+        path: &str,
+        request: &(impl Serialize + Sync),
+        deadline: tokio::time::Instant,
+    ) -> Result<Vec<Felt>, SignError> {
+        let mut retry_delay = self.options.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let mut last_error = None;
+            for endpoint in &self.endpoints {
+                match endpoint.sign(path, request).await {
+                    Ok(signature) => return Ok(signature),
+                    // Other endpoints front the same key and policy.
+                    Err(error @ SignError::Rejected { .. }) => return Err(error),
+                    Err(error) => {
+                        tracing::warn!(endpoint=%endpoint.label, %error, "Remote signer request failed");
+                        last_error = Some(error);
+                    }
+                }
+            }
+
+            if attempt == self.options.retries
+                || tokio::time::Instant::now() + retry_delay >= deadline
+            {
+                return Err(last_error.expect("There is at least one endpoint"));
+            }
+            attempt += 1;
+            tracing::debug!(%attempt, ?retry_delay, "Retrying remote signer request");
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AttestationSigner for RemoteSigner {
+    async fn sign(&self, request: &SigningRequest) -> Result<Vec<Felt>, SignError> {
+        let request = match self.options.protocol {
+            RemoteSignerProtocol::V1 => RemoteSignRequest {
+                version: None,
+                transaction: &request.transaction,
+                chain_id: request.chain_id,
+                transaction_hash: None,
+                attestation: None,
+            },
+            RemoteSignerProtocol::V2 => RemoteSignRequest {
+                version: Some(2),
+                transaction: &request.transaction,
+                chain_id: request.chain_id,
+                transaction_hash: Some(request.transaction_hash),
+                attestation: Some(&request.context),
+            },
+        };
+        self.sign_with_failover("/sign", &request).await
+    }
+
+    async fn sign_typed_data(
+        &self,
+        request: &TypedDataSigningRequest,
+    ) -> Result<Vec<Felt>, SignError> {
+        let request = RemoteSignTypedDataRequest {
+            typed_data: &request.typed_data,
+            account_address: request.account_address,
+            message_hash: request.message_hash,
+            attestation: &request.context,
+        };
+        self.sign_with_failover("/sign_typed_data", &request).await
+    }
+
+    async fn public_key(&self) -> Result<Felt, SignError> {
+        let mut last_error = None;
+        for endpoint in &self.endpoints {
+            match endpoint.get_public_key().await {
+                Ok(public_key) => return Ok(public_key),
+                Err(error) => {
+                    tracing::warn!(endpoint=%endpoint.label, %error, "Remote signer public key request failed");
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.expect("There is at least one endpoint"))
+    }
 
     fn is_interactive(&self, _context: SignerInteractivityContext<'_>) -> bool {
         true
     }
 }
 
+/// Request body sent to the `/sign` endpoint of remote signers.
 #[derive(Serialize)]
-struct SignRequest {
-    transaction: BroadcastedInvokeTransactionV3,
+struct RemoteSignRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u8>,
+    transaction: &'a BroadcastedInvokeTransactionV3,
     chain_id: Felt,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_hash: Option<Felt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<&'a AttestationContext>,
+}
+
+/// Request body sent to the `/sign_typed_data` endpoint of remote signers.
+#[derive(Serialize)]
+struct RemoteSignTypedDataRequest<'a> {
+    typed_data: &'a TypedData,
+    account_address: Felt,
+    message_hash: Felt,
+    attestation: &'a AttestationContext,
 }
 
 #[derive(Deserialize)]
 struct SignHashResponse {
     signature: Vec<Felt>,
 }
+
+#[derive(Deserialize)]
+struct PublicKeyResponse {
+    public_key: Felt,
+}
+
+/// Error body returned by remote signers refusing to sign a request.
+#[derive(Deserialize)]
+struct SignErrorResponse {
+    error: SignErrorDetails,
+}
+
+#[derive(Deserialize)]
+struct SignErrorDetails {
+    code: String,
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::core::types::{DataAvailabilityMode, ResourceBounds, ResourceBoundsMapping};
+    use starknet_rust::macros::felt;
+
+    use super::*;
+
+    const SIGNATURE: [Felt; 2] = [felt!("0x1"), felt!("0x2")];
+    const PUBLIC_KEY: Felt = felt!("0x1234");
+
+    fn transaction() -> BroadcastedInvokeTransactionV3 {
+        let no_resources = ResourceBounds {
+            max_amount: 0,
+            max_price_per_unit: 0,
+        };
+        BroadcastedInvokeTransactionV3 {
+            sender_address: felt!("0xfeedbeef"),
+            calldata: vec![],
+            signature: vec![],
+            nonce: Felt::ZERO,
+            resource_bounds: ResourceBoundsMapping {
+                l1_gas: no_resources.clone(),
+                l1_data_gas: no_resources.clone(),
+                l2_gas: no_resources,
+            },
+            tip: 0,
+            paymaster_data: vec![],
+            account_deployment_data: vec![],
+            nonce_data_availability_mode: DataAvailabilityMode::L1,
+            fee_data_availability_mode: DataAvailabilityMode::L1,
+            is_query: false,
+        }
+    }
+
+    fn request() -> SigningRequest {
+        SigningRequest {
+            transaction_hash: Felt::ONE,
+            transaction: transaction(),
+            chain_id: Felt::ZERO,
+            context: AttestationContext {
+                staker_address: felt!("0xdeadbeef"),
+                epoch_id: 1,
+                block_number: 2,
+                block_hash: felt!("0x123456789abcdef"),
+                attestation_contract_address: felt!("0xabcdef"),
+            },
+        }
+    }
+
+    /// Spawns a signer returning `response` on a Unix domain socket.
+    fn spawn_signer(name: &str, response: serde_json::Value) -> (url::Url, std::path::PathBuf) {
+        let socket_path = std::env::temp_dir().join(format!(
+            "starknet-validator-attestation-{name}-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        let app = axum::Router::new()
+            .route(
+                "/sign",
+                axum::routing::post(move || async move {
+                    if response.get("error").is_some() {
+                        (axum::http::StatusCode::FORBIDDEN, axum::Json(response))
+                    } else {
+                        (axum::http::StatusCode::OK, axum::Json(response))
+                    }
+                }),
+            )
+            .route(
+                "/get_public_key",
+                axum::routing::get(|| async {
+                    axum::Json(serde_json::json!({ "public_key": PUBLIC_KEY }))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let url = url::Url::parse(&format!("unix://{}", socket_path.display())).unwrap();
+        (url, socket_path)
+    }
+
+    #[tokio::test]
+    async fn remote_signer_over_unix_socket() {
+        let (url, socket_path) =
+            spawn_signer("signer", serde_json::json!({ "signature": SIGNATURE }));
+
+        let signer = RemoteSigner::new(
+            vec![url],
+            RemoteSignerOptions {
+                protocol: RemoteSignerProtocol::V2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let signature = signer.sign(&request()).await;
+        std::fs::remove_file(&socket_path).unwrap();
+
+        assert_eq!(signature.unwrap(), SIGNATURE.to_vec());
+    }
+
+    #[tokio::test]
+    async fn remote_signer_failover() {
+        let unavailable = url::Url::parse("unix:///nonexistent/signer.sock").unwrap();
+        let (url, socket_path) =
+            spawn_signer("failover", serde_json::json!({ "signature": SIGNATURE }));
+
+        let signer = RemoteSigner::new(
+            vec![unavailable.clone(), url],
+            RemoteSignerOptions::default(),
+        )
+        .unwrap();
+        let signature = signer.sign(&request()).await;
+        let public_key = signer.public_key().await;
+        std::fs::remove_file(&socket_path).unwrap();
+        assert_eq!(signature.unwrap(), SIGNATURE.to_vec());
+        assert_eq!(public_key.unwrap(), PUBLIC_KEY);
+
+        // Retries are exhausted if no endpoint is available.
+        let signer = RemoteSigner::new(
+            vec![unavailable],
+            RemoteSignerOptions {
+                retries: 2,
+                retry_delay: Duration::from_millis(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_matches::assert_matches!(
+            signer.sign(&request()).await,
+            Err(SignError::Transport(_))
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_signer_failover_is_bounded_by_deadline() {
+        // Accepts connections but never responds.
+        let socket_path = std::env::temp_dir().join(format!(
+            "starknet-validator-attestation-hanging-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        let hanging = url::Url::parse(&format!("unix://{}", socket_path.display())).unwrap();
+        let unavailable = url::Url::parse("unix:///nonexistent/signer.sock").unwrap();
+
+        let options = RemoteSignerOptions {
+            retries: 100,
+            retry_delay: Duration::from_millis(50),
+            deadline: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let signer = RemoteSigner::new(vec![hanging], options.clone()).unwrap();
+        assert_matches::assert_matches!(
+            signer.sign(&request()).await,
+            Err(SignError::DeadlineExceeded(_))
+        );
+        // Retries stop before the deadline, with the last error.
+        let signer = RemoteSigner::new(vec![unavailable], options).unwrap();
+        assert_matches::assert_matches!(
+            signer.sign(&request()).await,
+            Err(SignError::Transport(_))
+        );
+        std::fs::remove_file(&socket_path).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn remote_signer_rejection_is_not_retried() {
+        let (rejecting, rejecting_path) = spawn_signer(
+            "rejecting",
+            serde_json::json!({ "error": { "code": "TIP_TOO_HIGH", "message": "Tip too high" } }),
+        );
+        let (url, socket_path) =
+            spawn_signer("accepting", serde_json::json!({ "signature": SIGNATURE }));
+
+        let signer =
+            RemoteSigner::new(vec![rejecting, url], RemoteSignerOptions::default()).unwrap();
+        let signature = signer.sign(&request()).await;
+        std::fs::remove_file(&rejecting_path).unwrap();
+        std::fs::remove_file(&socket_path).unwrap();
+
+        assert_matches::assert_matches!(
+            signature,
+            Err(SignError::Rejected { code, .. }) if code == "TIP_TOO_HIGH"
+        );
+    }
+
+    #[test]
+    fn invalid_unix_socket_url() {
+        let url = url::Url::parse("unix://signer.sock").unwrap();
+
+        assert!(RemoteSigner::new(vec![url], RemoteSignerOptions::default()).is_err());
+    }
+}
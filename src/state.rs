@@ -1,10 +1,13 @@
-use std::{cmp::Ordering, time::SystemTime};
+use std::{cmp::Ordering, ops::Range, time::SystemTime};
 
 use anyhow::Context;
 use starknet_rust::core::types::{Felt, TransactionExecutionStatus, TransactionStatus};
 
 use crate::{
-    attestation_info::AttestationInfo, events::AttestationEvent, signer::AttestationSigner,
+    attestation_info::AttestationInfo,
+    events::AttestationEvent,
+    jsonrpc::{AttestationTransaction, ClientError, SponsoredExecution},
+    signer::AttestationSigner,
     tip::TipCalculationParams,
 };
 
@@ -15,14 +18,39 @@ use crate::{
 /// 10.
 const MIN_ATTESTATION_WINDOW: u64 = 11;
 
+/// Whether attestation transactions are sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Submission {
+    #[default]
+    Enabled,
+    /// Epochs are still tracked, but no attestation transactions are sent.
+    Paused,
+}
+
+/// The blocks in which the attestation of `block_to_attest` can be included.
+pub fn attestation_window(block_to_attest: u64, attestation_window: u16) -> Range<u64> {
+    block_to_attest + MIN_ATTESTATION_WINDOW..block_to_attest + attestation_window as u64
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct AttestationParams {
+    block_to_attest: u64,
     block_hash: Felt,
     start_of_attestation_window: u64,
     end_of_attestation_window: u64,
 }
 
 impl AttestationParams {
+    pub fn new(block_to_attest: u64, block_hash: Felt, attestation_window: u16) -> Self {
+        let window = self::attestation_window(block_to_attest, attestation_window);
+        Self {
+            block_to_attest,
+            block_hash,
+            start_of_attestation_window: window.start,
+            end_of_attestation_window: window.end,
+        }
+    }
+
     pub fn in_window(&self, block_number: u64) -> Ordering {
         use std::cmp::Ordering;
 
@@ -45,17 +73,39 @@ pub enum State {
     Attesting {
         attestation_info: AttestationInfo,
         attestation_params: AttestationParams,
+        /// Why previous attempts to submit the attestation have failed.
+        failed_attempts: Vec<String>,
+        /// An outside execution the paymaster may still relay. No other
+        /// attestation is sent until it has expired.
+        sponsored_execution: Option<SponsoredExecution>,
     },
     AttestationSubmitted {
         attestation_info: AttestationInfo,
         attestation_params: AttestationParams,
         transaction_hash: Felt,
+        tip: Option<u64>,
     },
     WaitingForNextEpoch {
         attestation_info: AttestationInfo,
+        outcome: EpochOutcome,
     },
 }
 
+/// How the attestation of an epoch ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpochOutcome {
+    /// Our attestation transaction has been confirmed.
+    Confirmed {
+        transaction_hash: Felt,
+        tip: Option<u64>,
+    },
+    /// The attestation is done, but not by a transaction we have seen
+    /// confirmed.
+    AttestedExternally,
+    /// The attestation window expired without an attestation.
+    Missed,
+}
+
 impl State {
     pub fn from_attestation_info(attestation_info: AttestationInfo) -> Self {
         let block_to_attest = attestation_info.calculate_expected_attestation_block();
@@ -75,7 +125,30 @@ impl State {
         }
     }
 
-    fn attestation_info(&self) -> &AttestationInfo {
+    /// Whether our attestation is still to be confirmed while the attestation
+    /// window is open at `block_number`.
+    pub fn attestation_pending(&self, block_number: u64) -> bool {
+        match self {
+            State::Attesting {
+                attestation_params, ..
+            }
+            | State::AttestationSubmitted {
+                attestation_params, ..
+            } => attestation_params.in_window(block_number) == Ordering::Equal,
+            _ => false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            State::BeforeBlockToAttest { .. } => "before_block_to_attest",
+            State::Attesting { .. } => "attesting",
+            State::AttestationSubmitted { .. } => "attestation_submitted",
+            State::WaitingForNextEpoch { .. } => "waiting_for_next_epoch",
+        }
+    }
+
+    pub fn attestation_info(&self) -> &AttestationInfo {
         match self {
             State::BeforeBlockToAttest {
                 attestation_info, ..
@@ -86,7 +159,76 @@ impl State {
             State::AttestationSubmitted {
                 attestation_info, ..
             } => attestation_info,
-            State::WaitingForNextEpoch { attestation_info } => attestation_info,
+            State::WaitingForNextEpoch {
+                attestation_info, ..
+            } => attestation_info,
+        }
+    }
+
+    pub fn epoch_id(&self) -> u64 {
+        self.attestation_info().epoch_id
+    }
+
+    pub fn staker_address(&self) -> Felt {
+        self.attestation_info().staker_address
+    }
+
+    pub fn block_to_attest(&self) -> u64 {
+        self.attestation_info()
+            .calculate_expected_attestation_block()
+    }
+
+    /// The blocks in which the attestation can be included.
+    pub fn attestation_window(&self) -> Range<u64> {
+        attestation_window(
+            self.block_to_attest(),
+            self.attestation_info().attestation_window,
+        )
+    }
+
+    /// The hash of the block to attest, once it is known.
+    pub fn block_hash(&self) -> Option<Felt> {
+        match self {
+            State::Attesting {
+                attestation_params, ..
+            }
+            | State::AttestationSubmitted {
+                attestation_params, ..
+            } => Some(attestation_params.block_hash),
+            _ => None,
+        }
+    }
+
+    /// Our attestation transaction waiting for confirmation, if any.
+    pub fn submitted_transaction(&self) -> Option<AttestationTransaction> {
+        match self {
+            State::AttestationSubmitted {
+                transaction_hash,
+                tip,
+                ..
+            } => Some(AttestationTransaction {
+                transaction_hash: *transaction_hash,
+                tip: *tip,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Why attempts to submit the attestation have failed since the last
+    /// submitted transaction.
+    pub fn failed_attempts(&self) -> &[String] {
+        match self {
+            State::Attesting {
+                failed_attempts, ..
+            } => failed_attempts,
+            _ => &[],
+        }
+    }
+
+    pub fn outcome(&self) -> Option<&EpochOutcome> {
+        match self {
+            State::WaitingForNextEpoch { outcome, .. } => Some(outcome),
+            _ => None,
         }
     }
 
@@ -97,15 +239,17 @@ impl State {
                 < attestation_info.current_epoch_starting_block + attestation_info.epoch_len
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn handle_new_block_header<
         C: crate::jsonrpc::Client + Send + Sync + 'static,
-        // S: starknet_rust::signers::Signer + Send + Sync + 'static,
+        S: AttestationSigner + ?Sized,
     >(
         self,
         client: &C,
         operational_address: Felt,
-        signer: &AttestationSigner,
+        signer: &S,
         tip_calculation_params: &TipCalculationParams,
+        submission: Submission,
         block_number: u64,
         block_hash: Felt,
     ) -> anyhow::Result<Self> {
@@ -131,20 +275,6 @@ impl State {
                 "New epoch started"
             );
 
-            // Update operational account balance at the start of new epoch
-            if let Ok(balance) = client.get_strk_balance(operational_address).await {
-                let balance_strk = balance as f64 / 1e18;
-                metrics::gauge!("validator_attestation_operational_account_balance_strk")
-                    .set(balance_strk);
-                tracing::debug!(
-                    epoch_id=%attestation_info.epoch_id,
-                    %balance_strk,
-                    "Updated operational account balance for new epoch",
-                );
-            } else {
-                tracing::warn!("Failed to update operational account balance for new epoch");
-            }
-
             State::from_attestation_info(attestation_info)
         };
 
@@ -163,11 +293,13 @@ impl State {
                     let attestation_window = attestation_info.attestation_window;
                     State::Attesting {
                         attestation_info,
-                        attestation_params: AttestationParams {
+                        attestation_params: AttestationParams::new(
+                            block_to_attest,
                             block_hash,
-                            start_of_attestation_window: block_number + MIN_ATTESTATION_WINDOW,
-                            end_of_attestation_window: block_number + attestation_window as u64,
-                        },
+                            attestation_window,
+                        ),
+                        failed_attempts: Vec::new(),
+                        sponsored_execution: None,
                     }
                 }
                 // We're past the block on the block header subscription.
@@ -180,13 +312,13 @@ impl State {
                             let attestation_window = attestation_info.attestation_window;
                             State::Attesting {
                                 attestation_info,
-                                attestation_params: AttestationParams {
+                                attestation_params: AttestationParams::new(
+                                    block_to_attest,
                                     block_hash,
-                                    start_of_attestation_window: block_to_attest
-                                        + MIN_ATTESTATION_WINDOW,
-                                    end_of_attestation_window: block_to_attest
-                                        + attestation_window as u64,
-                                },
+                                    attestation_window,
+                                ),
+                                failed_attempts: Vec::new(),
+                                sponsored_execution: None,
                             }
                         })?
                 }
@@ -194,32 +326,46 @@ impl State {
             State::Attesting {
                 attestation_info,
                 attestation_params,
+                failed_attempts,
+                sponsored_execution,
             } => match attestation_params.in_window(block_number) {
                 Ordering::Less => State::Attesting {
                     attestation_info,
                     attestation_params,
+                    failed_attempts,
+                    sponsored_execution,
                 },
                 Ordering::Equal => {
                     Self::check_and_submit_attestation(
                         client,
                         signer,
                         tip_calculation_params,
+                        submission,
                         attestation_info,
                         attestation_params,
+                        failed_attempts,
+                        sponsored_execution,
                     )
                     .await?
                 }
                 Ordering::Greater => {
                     // We're past the attestation window
-                    Self::check_and_mark_epoch_as_missed(client, attestation_info.staker_address)
-                        .await;
-                    State::WaitingForNextEpoch { attestation_info }
+                    let outcome = Self::check_and_mark_epoch_as_missed(
+                        client,
+                        attestation_info.staker_address,
+                    )
+                    .await;
+                    State::WaitingForNextEpoch {
+                        attestation_info,
+                        outcome,
+                    }
                 }
             },
             State::AttestationSubmitted {
                 attestation_info,
                 attestation_params,
                 transaction_hash,
+                tip,
             } => {
                 match attestation_params.in_window(block_number) {
                     Ordering::Less | Ordering::Equal => {
@@ -236,6 +382,7 @@ impl State {
                                     attestation_info,
                                     attestation_params,
                                     transaction_hash,
+                                    tip,
                                 }
                             }
                             Ok(
@@ -246,6 +393,7 @@ impl State {
                                 attestation_info,
                                 attestation_params,
                                 transaction_hash,
+                                tip,
                             },
                             Ok(
                                 TransactionStatus::AcceptedOnL2(execution_result)
@@ -266,8 +414,11 @@ impl State {
                                     client,
                                     signer,
                                     tip_calculation_params,
+                                    submission,
                                     attestation_info,
                                     attestation_params,
+                                    Vec::new(),
+                                    None,
                                 )
                                 .await?
                             }
@@ -281,41 +432,84 @@ impl State {
                                     "validator_attestation_attestation_confirmed_count"
                                 )
                                 .increment(1);
-                                Self::WaitingForNextEpoch { attestation_info }
+                                Self::WaitingForNextEpoch {
+                                    attestation_info,
+                                    outcome: EpochOutcome::Confirmed {
+                                        transaction_hash,
+                                        tip,
+                                    },
+                                }
                             }
                         }
                     }
                     Ordering::Greater => {
                         // Check if attestation was actually confirmed before marking epoch as missed
-                        Self::check_and_mark_epoch_as_missed(
+                        let outcome = Self::check_and_mark_epoch_as_missed(
                             client,
                             attestation_info.staker_address,
                         )
                         .await;
 
-                        State::WaitingForNextEpoch { attestation_info }
+                        State::WaitingForNextEpoch {
+                            attestation_info,
+                            outcome,
+                        }
                     }
                 }
             }
-            State::WaitingForNextEpoch { attestation_info } => {
-                State::WaitingForNextEpoch { attestation_info }
-            }
+            State::WaitingForNextEpoch {
+                attestation_info,
+                outcome,
+            } => State::WaitingForNextEpoch {
+                attestation_info,
+                outcome,
+            },
         })
     }
 
-    async fn check_and_submit_attestation<C: crate::jsonrpc::Client + Send + Sync + 'static>(
+    #[allow(clippy::too_many_arguments)]
+    async fn check_and_submit_attestation<
+        C: crate::jsonrpc::Client + Send + Sync + 'static,
+        S: AttestationSigner + ?Sized,
+    >(
         client: &C,
-        signer: &AttestationSigner,
+        signer: &S,
         tip_calculation_params: &TipCalculationParams,
+        submission: Submission,
         attestation_info: AttestationInfo,
         attestation_params: AttestationParams,
+        mut failed_attempts: Vec<String>,
+        sponsored_execution: Option<SponsoredExecution>,
     ) -> anyhow::Result<Self> {
         let attestation_done = client
             .attestation_done_in_current_epoch(attestation_info.staker_address)
             .await
             .context("Checking attestation status")?;
 
-        let next_state = if !attestation_done {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        // Any other attestation could be sent in addition to this one.
+        let pending_execution =
+            sponsored_execution.filter(|execution| execution.execute_before >= now);
+
+        let next_state = if !attestation_done && submission == Submission::Paused {
+            tracing::info!(epoch_id=%attestation_info.epoch_id, "Attestation submission is paused, not sending attestation transaction");
+            Self::Attesting {
+                attestation_info,
+                attestation_params,
+                failed_attempts,
+                sponsored_execution: pending_execution,
+            }
+        } else if let (false, Some(execution)) = (attestation_done, pending_execution) {
+            tracing::info!(message_hash=?execution.message_hash, execute_before=%execution.execute_before, "Sponsored attestation may still be relayed, not sending another attestation transaction");
+            Self::Attesting {
+                attestation_info,
+                attestation_params,
+                failed_attempts,
+                sponsored_execution: pending_execution,
+            }
+        } else if !attestation_done {
             match Self::submit_attestation(
                 client,
                 signer,
@@ -325,43 +519,60 @@ impl State {
             )
             .await
             {
-                Ok(transaction_hash) => Self::AttestationSubmitted {
-                    attestation_info,
-                    attestation_params,
-                    transaction_hash,
-                },
-                Err(_) => Self::Attesting {
+                Ok(transaction) => Self::AttestationSubmitted {
                     attestation_info,
                     attestation_params,
+                    transaction_hash: transaction.transaction_hash,
+                    tip: transaction.tip,
                 },
+                Err(error) => {
+                    failed_attempts.push(format!("{error:#}"));
+                    let sponsored_execution = match error.downcast_ref() {
+                        Some(ClientError::MaybeSent { execution, .. }) => Some(*execution),
+                        _ => None,
+                    };
+                    Self::Attesting {
+                        attestation_info,
+                        attestation_params,
+                        failed_attempts,
+                        sponsored_execution,
+                    }
+                }
             }
         } else {
             tracing::debug!("Attestation already done");
-            Self::WaitingForNextEpoch { attestation_info }
+            Self::WaitingForNextEpoch {
+                attestation_info,
+                outcome: EpochOutcome::AttestedExternally,
+            }
         };
 
         Ok(next_state)
     }
 
-    async fn submit_attestation<C: crate::jsonrpc::Client + Send + Sync + 'static>(
+    async fn submit_attestation<
+        C: crate::jsonrpc::Client + Send + Sync + 'static,
+        S: AttestationSigner + ?Sized,
+    >(
         client: &C,
-        signer: &AttestationSigner,
+        signer: &S,
         tip_calculation_params: &TipCalculationParams,
         attestation_info: &AttestationInfo,
         attestation_params: &AttestationParams,
-    ) -> anyhow::Result<Felt> {
+    ) -> anyhow::Result<AttestationTransaction> {
         tracing::debug!(block_hash=?attestation_params.block_hash, "Sending attestation transaction");
         let result = client
             .attest(
-                attestation_info.operational_address,
                 signer,
                 tip_calculation_params,
+                attestation_info,
+                attestation_params.block_to_attest,
                 attestation_params.block_hash,
             )
             .await;
         match result {
-            Ok(transaction_hash) => {
-                tracing::info!(?transaction_hash, "Attestation transaction sent");
+            Ok(transaction) => {
+                tracing::info!(transaction_hash=?transaction.transaction_hash, tip=?transaction.tip, "Attestation transaction sent");
 
                 metrics::gauge!("validator_attestation_last_attestation_timestamp_seconds").set(
                     SystemTime::now()
@@ -370,7 +581,7 @@ impl State {
                 );
                 metrics::counter!("validator_attestation_attestation_submitted_count").increment(1);
 
-                Ok(transaction_hash)
+                Ok(transaction)
             }
             Err(err) => {
                 tracing::error!(error = ?err, "Failed to send attestation transaction");
@@ -384,7 +595,7 @@ impl State {
     async fn check_and_mark_epoch_as_missed<C: crate::jsonrpc::Client + Send + Sync + 'static>(
         client: &C,
         staker_address: Felt,
-    ) {
+    ) -> EpochOutcome {
         // Check if attestation was already done for this epoch before marking as missed
         match client
             .attestation_done_in_current_epoch(staker_address)
@@ -395,11 +606,13 @@ impl State {
                     "Attestation window expired without submitting or confirming an attestation"
                 );
                 metrics::counter!("validator_attestation_missed_epochs_count").increment(1);
+                EpochOutcome::Missed
             }
             Ok(true) => {
                 tracing::info!(
                     "Attestation window expired but attestation was already completed on this epoch"
                 );
+                EpochOutcome::AttestedExternally
             }
             Err(error) => {
                 tracing::error!(
@@ -407,6 +620,7 @@ impl State {
                     "Failed to check attestation status, assuming missed"
                 );
                 metrics::counter!("validator_attestation_missed_epochs_count").increment(1);
+                EpochOutcome::Missed
             }
         }
     }
@@ -444,6 +658,8 @@ impl State {
             State::Attesting {
                 attestation_info,
                 attestation_params,
+                failed_attempts,
+                sponsored_execution,
             } => {
                 if attestation_info.staker_address == staker_address
                     && attestation_info.epoch_id == epoch_id
@@ -452,12 +668,17 @@ impl State {
                         "validator_attestation_attestation_confirmations_observed_count"
                     )
                     .increment(1);
-                    Self::WaitingForNextEpoch { attestation_info }
+                    Self::WaitingForNextEpoch {
+                        attestation_info,
+                        outcome: EpochOutcome::AttestedExternally,
+                    }
                 } else {
                     tracing::trace!(?staker_address, %epoch_id, "Skipping attestation successful event for other staker");
                     State::Attesting {
                         attestation_info,
                         attestation_params,
+                        failed_attempts,
+                        sponsored_execution,
                     }
                 }
             }
@@ -465,6 +686,7 @@ impl State {
                 attestation_info,
                 attestation_params,
                 transaction_hash,
+                tip,
             } => {
                 if attestation_info.staker_address == staker_address
                     && attestation_info.epoch_id == epoch_id
@@ -476,17 +698,27 @@ impl State {
                     .increment(1);
                     metrics::counter!("validator_attestation_attestation_confirmed_count")
                         .increment(1);
-                    Self::WaitingForNextEpoch { attestation_info }
+                    Self::WaitingForNextEpoch {
+                        attestation_info,
+                        outcome: EpochOutcome::Confirmed {
+                            transaction_hash,
+                            tip,
+                        },
+                    }
                 } else {
                     tracing::trace!(?staker_address, %epoch_id, "Skipping attestation successful event for other staker");
                     State::AttestationSubmitted {
                         attestation_info,
                         attestation_params,
                         transaction_hash,
+                        tip,
                     }
                 }
             }
-            State::WaitingForNextEpoch { attestation_info } => {
+            State::WaitingForNextEpoch {
+                attestation_info,
+                outcome,
+            } => {
                 if attestation_info.staker_address == staker_address
                     && attestation_info.epoch_id == epoch_id
                 {
@@ -497,7 +729,10 @@ impl State {
                 } else {
                     tracing::trace!(?staker_address, %epoch_id, "Skipping attestation successful event for other staker");
                 }
-                State::WaitingForNextEpoch { attestation_info }
+                State::WaitingForNextEpoch {
+                    attestation_info,
+                    outcome,
+                }
             }
         }
     }
@@ -521,6 +756,7 @@ mod tests {
     #[test]
     fn test_attestation_params_in_window() {
         let attestation_params = AttestationParams {
+            block_to_attest: 0,
             block_hash: Felt::ZERO,
             start_of_attestation_window: 10,
             end_of_attestation_window: 20,
@@ -540,6 +776,211 @@ mod tests {
     const BLOCK_HASH: Felt = felt!("0x123456789abcdef");
     const TRANSACTION_HASH: Felt = felt!("0xabcdef123456789");
 
+    #[test]
+    fn test_attestation_pending_in_open_window() {
+        let attestation_info = AttestationInfo {
+            staker_address: STAKER_ADDRESS,
+            operational_address: OPERATIONAL_ADDRESS,
+            stake: STAKE,
+            epoch_len: 40,
+            epoch_id: EPOCH_ID,
+            current_epoch_starting_block: 20,
+            attestation_window: 20,
+        };
+        let state = State::AttestationSubmitted {
+            attestation_info: attestation_info.clone(),
+            attestation_params: AttestationParams::new(25, BLOCK_HASH, 20),
+            transaction_hash: TRANSACTION_HASH,
+            tip: Some(0),
+        };
+
+        assert!(!state.attestation_pending(35));
+        assert!(state.attestation_pending(36));
+        assert!(state.attestation_pending(44));
+        assert!(!state.attestation_pending(45));
+        assert!(
+            !State::WaitingForNextEpoch {
+                attestation_info,
+                outcome: EpochOutcome::Missed,
+            }
+            .attestation_pending(40)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paused_submission_does_not_attest() {
+        let attestation_info = AttestationInfo {
+            staker_address: STAKER_ADDRESS,
+            operational_address: OPERATIONAL_ADDRESS,
+            stake: STAKE,
+            epoch_id: EPOCH_ID,
+            current_epoch_starting_block: 0,
+            epoch_len: 40,
+            attestation_window: 20,
+        };
+        let block_to_attest = attestation_info.calculate_expected_attestation_block();
+        let client = MockClient::new(
+            attestation_info.clone(),
+            TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded),
+        );
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
+        let tip_calculation_params = TipCalculationParams::default();
+        let state = State::Attesting {
+            attestation_params: AttestationParams::new(
+                block_to_attest,
+                BLOCK_HASH,
+                attestation_info.attestation_window,
+            ),
+            attestation_info,
+            failed_attempts: Vec::new(),
+            sponsored_execution: None,
+        };
+
+        // First block within the attestation window, paused
+        let state = state
+            .handle_new_block_header(
+                &client,
+                OPERATIONAL_ADDRESS,
+                &signer,
+                &tip_calculation_params,
+                Submission::Paused,
+                block_to_attest + MIN_ATTESTATION_WINDOW,
+                BLOCK_HASH,
+            )
+            .await
+            .unwrap();
+        assert_matches!(&state, State::Attesting { .. });
+        assert!(!client.attestation_sent());
+
+        // Resumed
+        let state = state
+            .handle_new_block_header(
+                &client,
+                OPERATIONAL_ADDRESS,
+                &signer,
+                &tip_calculation_params,
+                Submission::Enabled,
+                block_to_attest + MIN_ATTESTATION_WINDOW + 1,
+                BLOCK_HASH,
+            )
+            .await
+            .unwrap();
+        assert_matches!(&state, State::AttestationSubmitted { transaction_hash, .. } if *transaction_hash == TRANSACTION_HASH);
+        assert!(client.attestation_sent());
+    }
+
+    #[tokio::test]
+    async fn test_maybe_sent_sponsored_attestation_is_not_resubmitted() {
+        let attestation_info = AttestationInfo {
+            staker_address: STAKER_ADDRESS,
+            operational_address: OPERATIONAL_ADDRESS,
+            stake: STAKE,
+            epoch_id: EPOCH_ID,
+            current_epoch_starting_block: 0,
+            epoch_len: 40,
+            attestation_window: 20,
+        };
+        let block_to_attest = attestation_info.calculate_expected_attestation_block();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let execution = SponsoredExecution {
+            message_hash: felt!("0x1234"),
+            execute_before: now + 600,
+        };
+        let mut client = MockClient::new(
+            attestation_info.clone(),
+            TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded),
+        );
+        client.maybe_sent = Some(execution);
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
+        let tip_calculation_params = TipCalculationParams::default();
+        let state = State::Attesting {
+            attestation_params: AttestationParams::new(
+                block_to_attest,
+                BLOCK_HASH,
+                attestation_info.attestation_window,
+            ),
+            attestation_info,
+            failed_attempts: Vec::new(),
+            sponsored_execution: None,
+        };
+
+        // The paymaster may have received the outside execution
+        let state = state
+            .handle_new_block_header(
+                &client,
+                OPERATIONAL_ADDRESS,
+                &signer,
+                &tip_calculation_params,
+                Submission::Enabled,
+                block_to_attest + MIN_ATTESTATION_WINDOW,
+                BLOCK_HASH,
+            )
+            .await
+            .unwrap();
+        assert_matches!(&state, State::Attesting { sponsored_execution: Some(e), .. } if *e == execution);
+        assert!(client.attestation_sent());
+        assert_eq!(state.failed_attempts().len(), 1);
+
+        // Not sent again while the outside execution can still be relayed
+        client.clear_flags();
+        client.maybe_sent = None;
+        let state = state
+            .handle_new_block_header(
+                &client,
+                OPERATIONAL_ADDRESS,
+                &signer,
+                &tip_calculation_params,
+                Submission::Enabled,
+                block_to_attest + MIN_ATTESTATION_WINDOW + 1,
+                BLOCK_HASH,
+            )
+            .await
+            .unwrap();
+        assert_matches!(&state, State::Attesting { sponsored_execution: Some(e), .. } if *e == execution);
+        assert!(!client.attestation_sent());
+
+        // Sent again once it has expired
+        let State::Attesting {
+            attestation_info,
+            attestation_params,
+            failed_attempts,
+            ..
+        } = state
+        else {
+            unreachable!()
+        };
+        let state = State::Attesting {
+            attestation_info,
+            attestation_params,
+            failed_attempts,
+            sponsored_execution: Some(SponsoredExecution {
+                execute_before: now - 1,
+                ..execution
+            }),
+        };
+        let state = state
+            .handle_new_block_header(
+                &client,
+                OPERATIONAL_ADDRESS,
+                &signer,
+                &tip_calculation_params,
+                Submission::Enabled,
+                block_to_attest + MIN_ATTESTATION_WINDOW + 2,
+                BLOCK_HASH,
+            )
+            .await
+            .unwrap();
+        assert_matches!(&state, State::AttestationSubmitted { transaction_hash, .. } if *transaction_hash == TRANSACTION_HASH);
+        assert!(client.attestation_sent());
+    }
+
     #[tokio::test]
     async fn test_normal_flow_with_event_confirmation() {
         let initial_attestation_info = AttestationInfo {
@@ -566,9 +1007,9 @@ mod tests {
             next_attestation_info.clone(),
             TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded),
         );
-        let signer = AttestationSigner::new_local(LocalWallet::from_signing_key(
-            SigningKey::from_secret_scalar(felt!("0x123456789abcdef")),
-        ));
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
         let tip_calculation_params = TipCalculationParams::default();
         let state = State::from_attestation_info(initial_attestation_info.clone());
 
@@ -579,6 +1020,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 0,
                 BLOCK_HASH,
             )
@@ -599,12 +1041,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: initial_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: initial_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: initial_block_to_attest + initial_attestation_info.attestation_window as u64,
@@ -618,6 +1062,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW,
                 BLOCK_HASH,
             )
@@ -631,7 +1076,16 @@ mod tests {
             staker_address: STAKER_ADDRESS,
             epoch_id: EPOCH_ID,
         });
-        assert_matches!(state, State::WaitingForNextEpoch { .. });
+        assert_matches!(
+            state,
+            State::WaitingForNextEpoch {
+                outcome: EpochOutcome::Confirmed {
+                    transaction_hash,
+                    tip: Some(0),
+                },
+                ..
+            } if transaction_hash == TRANSACTION_HASH
+        );
 
         // First block of next epoch
         let state = state
@@ -640,6 +1094,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_attestation_info.epoch_len,
                 BLOCK_HASH,
             )
@@ -654,12 +1109,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 next_attestation_info.calculate_expected_attestation_block(),
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: next_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: next_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: next_block_to_attest + next_attestation_info.attestation_window as u64,
@@ -693,9 +1150,9 @@ mod tests {
             next_attestation_info.clone(),
             TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded),
         );
-        let signer = AttestationSigner::new_local(LocalWallet::from_signing_key(
-            SigningKey::from_secret_scalar(felt!("0x123456789abcdef")),
-        ));
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
         let tip_calculation_params = TipCalculationParams::default();
         let state = State::from_attestation_info(initial_attestation_info.clone());
 
@@ -706,6 +1163,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 0,
                 BLOCK_HASH,
             )
@@ -726,12 +1184,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: initial_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: initial_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: initial_block_to_attest + initial_attestation_info.attestation_window as u64,
@@ -745,6 +1205,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW,
                 BLOCK_HASH,
             )
@@ -760,12 +1221,22 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW + 1,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
-        assert_matches!(state, State::WaitingForNextEpoch { .. });
+        assert_matches!(
+            state,
+            State::WaitingForNextEpoch {
+                outcome: EpochOutcome::Confirmed {
+                    transaction_hash,
+                    tip: Some(0),
+                },
+                ..
+            } if transaction_hash == TRANSACTION_HASH
+        );
 
         // First block of next epoch
         let state = state
@@ -774,6 +1245,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_attestation_info.epoch_len,
                 BLOCK_HASH,
             )
@@ -788,12 +1260,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 next_attestation_info.calculate_expected_attestation_block(),
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: next_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: next_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: next_block_to_attest + next_attestation_info.attestation_window as u64,
@@ -829,9 +1303,9 @@ mod tests {
                 reason: "Some reason".into(),
             }),
         );
-        let signer = AttestationSigner::new_local(LocalWallet::from_signing_key(
-            SigningKey::from_secret_scalar(felt!("0x123456789abcdef")),
-        ));
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
         let tip_calculation_params = TipCalculationParams::default();
         let state = State::from_attestation_info(initial_attestation_info.clone());
 
@@ -842,6 +1316,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 0,
                 BLOCK_HASH,
             )
@@ -862,12 +1337,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: initial_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: initial_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: initial_block_to_attest + initial_attestation_info.attestation_window as u64,
@@ -881,6 +1358,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW,
                 BLOCK_HASH,
             )
@@ -897,6 +1375,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW + 1,
                 BLOCK_HASH,
             )
@@ -914,12 +1393,22 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW + 2,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
-        assert_matches!(&state, State::WaitingForNextEpoch { .. });
+        assert_matches!(
+            &state,
+            State::WaitingForNextEpoch {
+                outcome: EpochOutcome::Confirmed {
+                    transaction_hash,
+                    tip: Some(0),
+                },
+                ..
+            } if *transaction_hash == TRANSACTION_HASH
+        );
         assert!(!client.attestation_sent());
 
         // First block of next epoch
@@ -929,6 +1418,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_attestation_info.epoch_len,
                 BLOCK_HASH,
             )
@@ -943,12 +1433,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 next_attestation_info.calculate_expected_attestation_block(),
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: next_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: next_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: next_block_to_attest + next_attestation_info.attestation_window as u64,
@@ -982,9 +1474,9 @@ mod tests {
             next_attestation_info.clone(),
             TransactionStatus::AcceptedOnL1(ExecutionResult::Succeeded),
         );
-        let signer = AttestationSigner::new_local(LocalWallet::from_signing_key(
-            SigningKey::from_secret_scalar(felt!("0x123456789abcdef")),
-        ));
+        let signer = LocalWallet::from_signing_key(SigningKey::from_secret_scalar(felt!(
+            "0x123456789abcdef"
+        )));
         let tip_calculation_params = TipCalculationParams::default();
         let state = State::from_attestation_info(initial_attestation_info.clone());
 
@@ -995,12 +1487,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + 1,
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: initial_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: initial_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: initial_block_to_attest + initial_attestation_info.attestation_window as u64,
@@ -1015,6 +1509,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_block_to_attest + MIN_ATTESTATION_WINDOW,
                 BLOCK_HASH,
             )
@@ -1028,7 +1523,16 @@ mod tests {
             staker_address: STAKER_ADDRESS,
             epoch_id: EPOCH_ID,
         });
-        assert_matches!(state, State::WaitingForNextEpoch { .. });
+        assert_matches!(
+            state,
+            State::WaitingForNextEpoch {
+                outcome: EpochOutcome::Confirmed {
+                    transaction_hash,
+                    tip: Some(0),
+                },
+                ..
+            } if transaction_hash == TRANSACTION_HASH
+        );
 
         // First block of next epoch
         let state = state
@@ -1037,6 +1541,7 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 initial_attestation_info.epoch_len,
                 BLOCK_HASH,
             )
@@ -1051,12 +1556,14 @@ mod tests {
                 OPERATIONAL_ADDRESS,
                 &signer,
                 &tip_calculation_params,
+                Submission::Enabled,
                 next_attestation_info.calculate_expected_attestation_block(),
                 BLOCK_HASH,
             )
             .await
             .unwrap();
         assert_matches!(&state, State::Attesting { attestation_params, .. } if *attestation_params == AttestationParams {
+            block_to_attest: next_block_to_attest,
             block_hash: BLOCK_HASH,
             start_of_attestation_window: next_block_to_attest + MIN_ATTESTATION_WINDOW,
             end_of_attestation_window: next_block_to_attest + next_attestation_info.attestation_window as u64,
@@ -1069,6 +1576,9 @@ mod tests {
         attestation_sent: AtomicBool,
         block_hash_queried: AtomicBool,
         attestation_status: TransactionStatus,
+        /// Attestations fail as if the paymaster may have relayed this
+        /// outside execution.
+        maybe_sent: Option<SponsoredExecution>,
     }
 
     impl MockClient {
@@ -1078,6 +1588,7 @@ mod tests {
                 attestation_sent: AtomicBool::new(false),
                 block_hash_queried: AtomicBool::new(false),
                 attestation_status,
+                maybe_sent: None,
             }
         }
 
@@ -1100,20 +1611,31 @@ mod tests {
     }
 
     impl crate::jsonrpc::Client for MockClient {
-        async fn attest(
+        async fn attest<S: AttestationSigner + ?Sized>(
             &self,
-            operational_address: Felt,
-            _signer: &AttestationSigner,
+            _signer: &S,
             _tip_calculation_params: &TipCalculationParams,
+            attestation_info: &AttestationInfo,
+            _block_number: u64,
             block_hash: Felt,
-        ) -> Result<Felt, ClientError> {
-            assert_eq!(operational_address, OPERATIONAL_ADDRESS);
+        ) -> Result<AttestationTransaction, ClientError> {
+            assert_eq!(attestation_info.operational_address, OPERATIONAL_ADDRESS);
             assert_eq!(block_hash, BLOCK_HASH);
 
             self.attestation_sent
                 .store(true, std::sync::atomic::Ordering::Relaxed);
 
-            Ok(TRANSACTION_HASH)
+            if let Some(execution) = self.maybe_sent {
+                return Err(ClientError::MaybeSent {
+                    execution,
+                    error: anyhow::anyhow!("Paymaster timed out"),
+                });
+            }
+
+            Ok(AttestationTransaction {
+                transaction_hash: TRANSACTION_HASH,
+                tip: Some(0),
+            })
         }
 
         async fn attestation_status(
@@ -1155,5 +1677,11 @@ mod tests {
             // Return a mock balance of 100 STRK
             Ok(100_000_000_000_000_000_000) // 100 * 10^18
         }
+
+        async fn get_transaction_fee(&self, transaction_hash: Felt) -> Result<u128, ClientError> {
+            assert_eq!(transaction_hash, TRANSACTION_HASH);
+
+            Ok(1_000_000_000_000_000) // 0.001 STRK
+        }
     }
 }
@@ -0,0 +1,246 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use starknet_rust::core::types::Felt;
+
+use crate::{
+    attestation_info::AttestationInfo,
+    state::{State, Submission},
+};
+
+/// Number of recent block headers the block time estimate is based on.
+const SAMPLE_SIZE: usize = 100;
+
+/// Estimates the wall-clock time of blocks from recent header timestamps.
+#[derive(Debug, Default)]
+pub struct BlockTimes {
+    headers: VecDeque<(u64, u64)>,
+}
+
+impl BlockTimes {
+    pub fn record(&mut self, block_number: u64, timestamp: u64) {
+        // Reorgs make block numbers go backwards.
+        while let Some((last, _)) = self.headers.back()
+            && *last >= block_number
+        {
+            self.headers.pop_back();
+        }
+        if self.headers.len() == SAMPLE_SIZE {
+            self.headers.pop_front();
+        }
+        self.headers.push_back((block_number, timestamp));
+    }
+
+    pub fn latest_block_number(&self) -> Option<u64> {
+        self.headers.back().map(|(block_number, _)| *block_number)
+    }
+
+    /// Average seconds per block.
+    pub fn block_time(&self) -> Option<f64> {
+        let (first_block, first_timestamp) = self.headers.front()?;
+        let (last_block, last_timestamp) = self.headers.back()?;
+        if last_block == first_block {
+            return None;
+        }
+        Some(
+            last_timestamp.saturating_sub(*first_timestamp) as f64
+                / (last_block - first_block) as f64,
+        )
+    }
+
+    /// The estimated time of `block_number`.
+    pub fn predict(&self, block_number: u64) -> Option<DateTime<Utc>> {
+        let block_time = self.block_time()?;
+        let (last_block, last_timestamp) = self.headers.back()?;
+        let seconds =
+            *last_timestamp as f64 + (block_number as f64 - *last_block as f64) * block_time;
+        DateTime::from_timestamp(seconds.round() as i64, 0)
+    }
+}
+
+/// Whether restarting now would not interrupt an attestation pending in an
+/// open attestation window.
+pub fn safe_to_restart(state: &State, block_times: &BlockTimes) -> bool {
+    !block_times
+        .latest_block_number()
+        .is_some_and(|block_number| state.attestation_pending(block_number))
+}
+
+/// The attestation schedule of an epoch, with estimated times.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EpochSchedule {
+    pub epoch_id: u64,
+    pub block_to_attest: u64,
+    pub window_start: u64,
+    /// The first block after the window.
+    pub window_end: u64,
+    pub block_to_attest_time: Option<DateTime<Utc>>,
+    pub window_start_time: Option<DateTime<Utc>>,
+    pub window_end_time: Option<DateTime<Utc>>,
+}
+
+impl EpochSchedule {
+    fn new(attestation_info: &AttestationInfo, block_times: &BlockTimes) -> Self {
+        let block_to_attest = attestation_info.calculate_expected_attestation_block();
+        let window =
+            crate::state::attestation_window(block_to_attest, attestation_info.attestation_window);
+        Self {
+            epoch_id: attestation_info.epoch_id,
+            block_to_attest,
+            window_start: window.start,
+            window_end: window.end,
+            block_to_attest_time: block_times.predict(block_to_attest),
+            window_start_time: block_times.predict(window.start),
+            window_end_time: block_times.predict(window.end),
+        }
+    }
+
+    fn export(&self, epoch: &'static str) {
+        for (block, time) in [
+            ("block_to_attest", self.block_to_attest_time),
+            ("window_start", self.window_start_time),
+            ("window_end", self.window_end_time),
+        ] {
+            if let Some(time) = time {
+                metrics::gauge!(
+                    "validator_attestation_predicted_block_timestamp_seconds",
+                    "epoch" => epoch,
+                    "block" => block
+                )
+                .set(time.timestamp() as f64);
+            }
+        }
+    }
+}
+
+/// Status of the attestation, served on `/status`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Status {
+    pub staker_address: Felt,
+    pub state: &'static str,
+    pub latest_block_number: Option<u64>,
+    /// False while our attestation is pending in an open attestation window.
+    pub safe_to_restart: bool,
+    /// True while sending attestation transactions is paused.
+    pub submission_paused: bool,
+    /// Estimated average seconds per block.
+    pub block_time: Option<f64>,
+    pub current_epoch: EpochSchedule,
+    /// Assuming the current stake and epoch length.
+    pub next_epoch: Option<EpochSchedule>,
+}
+
+impl Status {
+    pub fn new(state: &State, block_times: &BlockTimes, submission: Submission) -> Self {
+        let attestation_info = state.attestation_info();
+        let next_epoch = attestation_info
+            .for_epoch(attestation_info.epoch_id + 1)
+            .map(|next| EpochSchedule::new(&next, block_times));
+        Self {
+            staker_address: attestation_info.staker_address,
+            state: state.name(),
+            latest_block_number: block_times.latest_block_number(),
+            safe_to_restart: safe_to_restart(state, block_times),
+            submission_paused: submission == Submission::Paused,
+            block_time: block_times.block_time(),
+            current_epoch: EpochSchedule::new(attestation_info, block_times),
+            next_epoch,
+        }
+    }
+
+    /// Updates the predicted time gauges.
+    pub fn export(&self) {
+        self.current_epoch.export("current");
+        if let Some(next_epoch) = &self.next_epoch {
+            next_epoch.export("next");
+        }
+    }
+
+    /// The attestation windows as an iCalendar feed.
+    pub fn to_ical(&self, now: DateTime<Utc>) -> String {
+        const FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+        let mut calendar = String::new();
+        calendar.push_str("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+        calendar.push_str("PRODID:-//starknet-validator-attestation//attestation windows//EN\r\n");
+        for epoch in std::iter::once(&self.current_epoch).chain(&self.next_epoch) {
+            let (Some(start), Some(end)) = (epoch.window_start_time, epoch.window_end_time) else {
+                continue;
+            };
+            let _ = write!(
+                calendar,
+                "BEGIN:VEVENT\r\n\
+                 UID:{staker:#x}-{epoch_id}@starknet-validator-attestation\r\n\
+                 DTSTAMP:{now}\r\n\
+                 DTSTART:{start}\r\n\
+                 DTEND:{end}\r\n\
+                 SUMMARY:Attestation window of epoch {epoch_id}\r\n\
+                 DESCRIPTION:Attesting block {block_to_attest} in blocks {window_start} to {window_last}\r\n\
+                 END:VEVENT\r\n",
+                staker = self.staker_address,
+                epoch_id = epoch.epoch_id,
+                now = now.format(FORMAT),
+                start = start.format(FORMAT),
+                end = end.format(FORMAT),
+                block_to_attest = epoch.block_to_attest,
+                window_start = epoch.window_start,
+                window_last = epoch.window_end - 1,
+            );
+        }
+        calendar.push_str("END:VCALENDAR\r\n");
+        calendar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_rust::macros::felt;
+
+    use super::*;
+
+    #[test]
+    fn block_time_prediction() {
+        let mut block_times = BlockTimes::default();
+        assert_eq!(block_times.predict(10), None);
+
+        block_times.record(100, 1_000_000);
+        block_times.record(101, 1_000_003);
+        // A reorg replaces block 101.
+        block_times.record(101, 1_000_002);
+        block_times.record(102, 1_000_004);
+
+        assert_eq!(block_times.block_time(), Some(2.0));
+        assert_eq!(
+            block_times.predict(112),
+            DateTime::from_timestamp(1_000_024, 0)
+        );
+    }
+
+    #[test]
+    fn ical() {
+        let mut block_times = BlockTimes::default();
+        block_times.record(0, 1_700_000_000);
+        block_times.record(10, 1_700_000_020);
+        let state = State::from_attestation_info(AttestationInfo {
+            staker_address: felt!("0xdeadbeef"),
+            operational_address: felt!("0xfeedbeef"),
+            stake: 1000,
+            epoch_len: 40,
+            epoch_id: 1,
+            current_epoch_starting_block: 40,
+            attestation_window: 20,
+        });
+        let status = Status::new(&state, &block_times, Submission::Enabled);
+
+        let ical = status.to_ical(DateTime::from_timestamp(1_700_000_000, 0).unwrap());
+
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ical.contains("UID:0xdeadbeef-1@starknet-validator-attestation\r\n"));
+        let start = block_times
+            .predict(status.current_epoch.window_start)
+            .unwrap();
+        assert!(ical.contains(&format!("DTSTART:{}\r\n", start.format("%Y%m%dT%H%M%SZ"))));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    }
+}
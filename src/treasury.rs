@@ -0,0 +1,313 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use starknet_rust::{
+    accounts::{Account, AccountError, ExecutionEncoding, SingleOwnerAccount},
+    core::{
+        types::{Call, Felt},
+        utils::get_selector_from_name,
+    },
+    providers::{JsonRpcClient, ProviderError, jsonrpc::HttpTransport},
+    signers::LocalWallet,
+};
+
+const FRI_PER_STRK: u128 = 1_000_000_000_000_000_000;
+
+/// Refill policy of the operational account.
+#[derive(Clone, Debug)]
+pub struct TopUpPolicy {
+    /// Top up if the balance is below this amount (in FRI).
+    pub low_water_mark: u128,
+    /// The amount (in FRI) transferred by a top-up.
+    pub amount: u128,
+    /// The maximum amount (in FRI) transferred in 24 hours.
+    pub daily_cap: u128,
+}
+
+/// Treasury account refilling the operational account with STRK when its
+/// balance runs low.
+pub struct Treasury {
+    account: SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    strk_contract_address: Felt,
+    policy: TopUpPolicy,
+    transfers: DailyTransfers,
+}
+
+impl Treasury {
+    /// Transfers are recorded in the file at `transfers_path`, so that the
+    /// daily cap holds across restarts.
+    pub fn new(
+        provider: JsonRpcClient<HttpTransport>,
+        signer: LocalWallet,
+        address: Felt,
+        chain_id: Felt,
+        strk_contract_address: Felt,
+        policy: TopUpPolicy,
+        transfers_path: &Path,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            account: SingleOwnerAccount::new(
+                provider,
+                signer,
+                address,
+                chain_id,
+                ExecutionEncoding::New,
+            ),
+            strk_contract_address,
+            policy,
+            transfers: DailyTransfers::load(transfers_path)?,
+        })
+    }
+
+    pub fn set_policy(&mut self, policy: TopUpPolicy) {
+        self.policy = policy;
+    }
+
+    /// Transfers STRK to `operational_address` if `balance` is below the low
+    /// water mark and the daily cap allows.
+    ///
+    /// Returns the hash of the transfer transaction, if any.
+    pub async fn top_up_if_needed(
+        &mut self,
+        operational_address: Felt,
+        balance: u128,
+    ) -> anyhow::Result<Option<Felt>> {
+        if balance >= self.policy.low_water_mark {
+            return Ok(None);
+        }
+
+        let now = SystemTime::now();
+        let transferred = self.transfers.total(now);
+        if transferred + self.policy.amount > self.policy.daily_cap {
+            tracing::warn!(
+                balance_strk=%format_strk(balance),
+                transferred_strk=%format_strk(transferred),
+                daily_cap_strk=%format_strk(self.policy.daily_cap),
+                "Operational account balance is low, but the daily top-up cap has been reached"
+            );
+            return Ok(None);
+        }
+
+        // Counted before sending: if sending fails after the transfer has been
+        // broadcast, it may still be executed.
+        self.transfers
+            .record(now, self.policy.amount)
+            .context("Recording top-up transfer")?;
+        let result = self
+            .account
+            .execute_v3(vec![Call {
+                to: self.strk_contract_address,
+                selector: get_selector_from_name("transfer").unwrap(),
+                calldata: vec![
+                    operational_address,
+                    // u256 as low and high 128 bits
+                    self.policy.amount.into(),
+                    Felt::ZERO,
+                ],
+            }])
+            .send()
+            .await;
+        let transaction_hash = match result {
+            Ok(result) => result.transaction_hash,
+            Err(error) => {
+                metrics::counter!("validator_attestation_treasury_top_up_failure_count")
+                    .increment(1);
+                // Rejected by the node or never sent.
+                if matches!(
+                    error,
+                    AccountError::Signing(_)
+                        | AccountError::Provider(ProviderError::StarknetError(_))
+                        | AccountError::FeeOutOfRange
+                ) && let Err(error) = self.transfers.cancel_last()
+                {
+                    tracing::error!(?error, "Failed to remove failed top-up transfer");
+                }
+                return Err(anyhow::Error::new(error).context("Sending top-up transfer"));
+            }
+        };
+
+        metrics::counter!("validator_attestation_treasury_top_up_count").increment(1);
+        tracing::info!(
+            ?transaction_hash,
+            balance_strk=%format_strk(balance),
+            amount_strk=%format_strk(self.policy.amount),
+            "Topped up operational account from treasury"
+        );
+
+        Ok(Some(transaction_hash))
+    }
+}
+
+/// Transfers made in the last 24 hours, persisted as JSON and rewritten
+/// atomically on each change.
+struct DailyTransfers {
+    path: PathBuf,
+    transfers: VecDeque<Transfer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Transfer {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    /// FRI, as a string: JSON numbers cannot hold every `u128`.
+    #[serde(with = "amount_format")]
+    amount: u128,
+}
+
+impl DailyTransfers {
+    const PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let transfers = match std::fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("Parsing treasury transfers {}", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Reading treasury transfers {}", path.display()));
+            }
+        };
+        Ok(Self {
+            path: path.to_owned(),
+            transfers,
+        })
+    }
+
+    fn total(&mut self, now: SystemTime) -> u128 {
+        let now = timestamp(now);
+        while let Some(transfer) = self.transfers.front()
+            && now.saturating_sub(transfer.timestamp) >= Self::PERIOD.as_secs()
+        {
+            self.transfers.pop_front();
+        }
+        self.transfers.iter().map(|transfer| transfer.amount).sum()
+    }
+
+    fn record(&mut self, now: SystemTime, amount: u128) -> anyhow::Result<()> {
+        self.transfers.push_back(Transfer {
+            timestamp: timestamp(now),
+            amount,
+        });
+        self.persist()
+    }
+
+    fn cancel_last(&mut self) -> anyhow::Result<()> {
+        self.transfers.pop_back();
+        self.persist()
+    }
+
+    fn persist(&self) -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let contents = serde_json::to_vec_pretty(&self.transfers)?;
+        let temporary_path = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temporary_path)
+            .with_context(|| format!("Creating {}", temporary_path.display()))?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, &self.path)
+            .with_context(|| format!("Writing treasury transfers {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+mod amount_format {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses a decimal STRK amount into FRI.
+pub fn parse_strk(value: &str) -> anyhow::Result<u128> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    anyhow::ensure!(
+        !whole.is_empty() || !fraction.is_empty(),
+        "Empty STRK amount"
+    );
+    anyhow::ensure!(
+        fraction.len() <= 18,
+        "STRK amounts have at most 18 decimals"
+    );
+    let whole: u128 = if whole.is_empty() { 0 } else { whole.parse()? };
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u128>()? * 10u128.pow(18 - fraction.len() as u32)
+    };
+
+    whole
+        .checked_mul(FRI_PER_STRK)
+        .and_then(|whole| whole.checked_add(fraction))
+        .context("STRK amount is too large")
+}
+
+fn format_strk(fri: u128) -> f64 {
+    fri as f64 / 1e18
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strk_amounts() {
+        assert_eq!(parse_strk("1").unwrap(), FRI_PER_STRK);
+        assert_eq!(
+            parse_strk("12.5").unwrap(),
+            12 * FRI_PER_STRK + FRI_PER_STRK / 2
+        );
+        assert_eq!(parse_strk(".000000000000000001").unwrap(), 1);
+        assert!(parse_strk("0.0000000000000000001").is_err());
+        assert!(parse_strk("").is_err());
+        assert!(parse_strk("-1").is_err());
+    }
+
+    #[test]
+    fn daily_transfers_expire_and_persist() {
+        let path = std::env::temp_dir().join(format!(
+            "starknet-validator-attestation-treasury-{}.json",
+            std::process::id()
+        ));
+        let mut transfers = DailyTransfers::load(&path).unwrap();
+        let start = SystemTime::now();
+        transfers.record(start, 10).unwrap();
+        transfers
+            .record(start + Duration::from_secs(60 * 60), 20)
+            .unwrap();
+        transfers
+            .record(start + Duration::from_secs(60 * 60), u128::MAX / 2)
+            .unwrap();
+        transfers.cancel_last().unwrap();
+
+        // As after a restart.
+        let mut transfers = DailyTransfers::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(transfers.total(start + Duration::from_secs(60 * 60)), 30);
+        assert_eq!(transfers.total(start + DailyTransfers::PERIOD), 20);
+        assert_eq!(
+            transfers.total(start + DailyTransfers::PERIOD + Duration::from_secs(60 * 60)),
+            0
+        );
+    }
+}